@@ -1,19 +1,17 @@
 // Small input images
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
+#[cfg(feature = "gpu")]
+use image_convolve::convolution::backends::gpu::{self, offscreen::context::GpuCtx};
 use image_convolve::{
-    convolution::{
-        backends::{
-            cpu,
-            gpu::{self, offscreen::context::GpuCtx},
-        },
-        strategy::prepare,
-    },
+    convolution::{backends::cpu, strategy::prepare},
+    kernel::KernelImpl,
     prelude::*,
 };
 
 fn impl_bench(c: &mut Criterion, name: &str, input: &str) {
     let input = prepare(input).unwrap();
+    #[cfg(feature = "gpu")]
     let gpu_ctx = GpuCtx::new(input.clone()).unwrap();
 
     let mut group = c.benchmark_group(name);
@@ -49,6 +47,31 @@ fn impl_bench(c: &mut Criterion, name: &str, input: &str) {
             },
         );
 
+        group.bench_with_input(
+            BenchmarkId::new("CPU Single SIMD", kernel),
+            kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::simd::Simd::from((input.clone(), *kernel)),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("CPU Single Blocked", kernel),
+            kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::blocked::Single::from((input.clone(), *kernel)),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        #[cfg(feature = "rayon")]
         group.bench_with_input(
             BenchmarkId::new("CPU Multi Rayon", kernel),
             kernel,
@@ -61,12 +84,93 @@ fn impl_bench(c: &mut Criterion, name: &str, input: &str) {
             },
         );
 
+        #[cfg(feature = "rayon")]
+        group.bench_with_input(
+            BenchmarkId::new("CPU Multi Chunked", kernel),
+            kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::multi::Chunked::from((input.clone(), *kernel)),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        #[cfg(feature = "gpu")]
         group.bench_with_input(
             BenchmarkId::new("GPU Offscreen", kernel),
             kernel,
             |bencher, kernel| {
                 bencher.iter_batched(
-                    || gpu::offscreen::Offscreen::new(gpu_ctx.clone(), *kernel).unwrap(),
+                    || {
+                        gpu::offscreen::Offscreen::new(gpu_ctx.clone(), *kernel, Options::default())
+                            .unwrap()
+                    },
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+    }
+
+    group.finish();
+}
+
+/// Blocking pays off once the input rows under the kernel no longer fit in the cache,
+/// so compare the direct backends with and without blocks as the kernel grows.
+fn kernel_size_bench(c: &mut Criterion) {
+    let input = prepare("images/1280x720.jpg").unwrap();
+    let mut group = c.benchmark_group("kernel-size");
+
+    for radius in [1, 2, 4, 8] {
+        let kernel = KernelImpl::gaussian(radius as f32 / 2., Some(radius)).unwrap();
+        let size = format!("{0}x{0}", 2 * radius + 1);
+
+        group.bench_with_input(
+            BenchmarkId::new("CPU Single SIMD", &size),
+            &kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::simd::Simd::from((input.clone(), kernel.clone())),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("CPU Single Blocked", &size),
+            &kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::blocked::Single::from((input.clone(), kernel.clone())),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        #[cfg(feature = "rayon")]
+        group.bench_with_input(
+            BenchmarkId::new("CPU Multi Rayon", &size),
+            &kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::multi::NestedIterators::from((input.clone(), kernel.clone())),
+                    |mut backend| backend.convolve(),
+                    criterion::BatchSize::SmallInput,
+                );
+            },
+        );
+
+        #[cfg(feature = "rayon")]
+        group.bench_with_input(
+            BenchmarkId::new("CPU Multi Blocked", &size),
+            &kernel,
+            |bencher, kernel| {
+                bencher.iter_batched(
+                    || cpu::blocked::Multi::from((input.clone(), kernel.clone())),
                     |mut backend| backend.convolve(),
                     criterion::BatchSize::SmallInput,
                 );
@@ -89,5 +193,11 @@ fn res_3840x2160(c: &mut Criterion) {
     impl_bench(c, "3840x2160", "images/3840x2160.jpg");
 }
 
-criterion_group!(benches, res_1280x720, res_1920x1080, res_3840x2160);
+criterion_group!(
+    benches,
+    res_1280x720,
+    res_1920x1080,
+    res_3840x2160,
+    kernel_size_bench
+);
 criterion_main!(benches);
@@ -0,0 +1,264 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use image::ImageFormat;
+use tracing::{error, info};
+
+use crate::{
+    convolution::strategy::{decode, prepare, save, save_with_format, write_to},
+    kernel::KernelImpl,
+    prelude::*,
+};
+
+/// The path standing for stdin as an input, and for stdout as an output.
+pub const STDIO: &str = "-";
+
+/// Characters which make an input a glob pattern rather than a path.
+const GLOB_CHARACTERS: [char; 3] = ['*', '?', '['];
+
+/// Whether the input is a glob pattern rather than a path.
+pub fn is_pattern(input: &Path) -> bool {
+    input.to_string_lossy().contains(GLOB_CHARACTERS)
+}
+
+/// Whether the path stands for stdin or stdout, see [`STDIO`].
+pub fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO)
+}
+
+/// Expand the inputs to the image files to process, in order.
+///
+/// Directories are replaced by the images directly within them, sorted by name,
+/// and glob patterns such as `images/*.jpg` by the paths matching them.
+/// Any other input is kept as is, such that missing files are reported when processing them.
+pub fn expand_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+
+    for input in inputs {
+        if input.is_dir() {
+            let mut images = std::fs::read_dir(input)?
+                .map(|entry| Ok(entry?.path()))
+                .collect::<Result<Vec<_>>>()?;
+            images.retain(|path| path.is_file() && ImageFormat::from_path(path).is_ok());
+            images.sort();
+
+            paths.extend(images);
+        } else if is_pattern(input) {
+            let pattern = input.to_string_lossy();
+            let matches = glob::glob(&pattern)
+                .map_err(|e| Error::InvalidInput(format!("`{pattern}`: {e}")))?;
+
+            for path in matches {
+                let path = path.map_err(std::io::Error::from)?;
+                if path.is_file() {
+                    paths.push(path);
+                }
+            }
+        } else {
+            paths.push(input.clone());
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Where to write the output for each input image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// A single output file, for a single input.
+    File(PathBuf),
+
+    /// A directory, where each output has the file name of its input.
+    Directory(PathBuf),
+
+    /// A naming template, where `{stem}`, `{ext}` and `{kernel}` are replaced
+    /// by the input file name without extension, the input extension and the kernel name.
+    /// For example `out/{stem}-{kernel}.{ext}`.
+    Template(String),
+}
+
+impl Output {
+    /// The output path for the given input, convolved with the named kernel.
+    pub fn path(&self, input: &Path, kernel: &str) -> PathBuf {
+        match self {
+            Output::File(path) => path.clone(),
+            Output::Directory(directory) => {
+                directory.join(input.file_name().unwrap_or(input.as_os_str()))
+            }
+            Output::Template(template) => {
+                let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+                let ext = input.extension().unwrap_or_default().to_string_lossy();
+
+                template
+                    .replace("{stem}", &stem)
+                    .replace("{ext}", &ext)
+                    .replace("{kernel}", kernel)
+                    .into()
+            }
+        }
+    }
+}
+
+/// Check that no output overwrites an input or the output of another input,
+/// before anything is written.
+///
+/// Catches e.g. an output directory which is also the input directory,
+/// and a template without `{stem}` for several inputs.
+/// Paths are compared after resolving their directories, stdin and stdout are ignored.
+///
+/// # Errors
+///
+/// [`Error::InvalidInput`] naming the first conflict found.
+pub fn check_jobs(jobs: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let inputs: HashSet<PathBuf> = jobs
+        .iter()
+        .filter(|(input, _)| !is_stdio(input))
+        .map(|(input, _)| resolve(input))
+        .collect();
+    let mut outputs = HashMap::new();
+
+    for (input, output) in jobs.iter().filter(|(_, output)| !is_stdio(output)) {
+        let resolved = resolve(output);
+
+        if inputs.contains(&resolved) {
+            return Err(Error::InvalidInput(format!(
+                "writing `{}` would overwrite an input",
+                output.display()
+            )));
+        }
+        if let Some(other) = outputs.insert(resolved, input) {
+            return Err(Error::InvalidInput(format!(
+                "`{}` and `{}` would both be written to `{}`",
+                other.display(),
+                input.display(),
+                output.display()
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// The path with its directory made absolute and free of links, if it exists,
+/// such that different spellings of the same file compare equal.
+fn resolve(path: &Path) -> PathBuf {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    match (directory.canonicalize(), path.file_name()) {
+        (Ok(directory), Some(name)) => directory.join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The outcome of processing a batch of images.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Output paths of the images which were processed.
+    pub succeeded: Vec<PathBuf>,
+
+    /// Input paths of the images which could not be processed, along with why.
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl Summary {
+    /// Turn the summary into an error if any image failed.
+    ///
+    /// A batch of a single image gives the error of that image,
+    /// larger batches give [`Error::Batch`].
+    pub fn into_result(mut self) -> Result<()> {
+        let total = self.succeeded.len() + self.failed.len();
+
+        match self.failed.len() {
+            0 => Ok(()),
+            1 if total == 1 => Err(self.failed.remove(0).1),
+            _ => Err(Error::Batch {
+                failures: self.failed,
+                total,
+            }),
+        }
+    }
+}
+
+/// Convolve each input image with the kernels in turn, and save the result
+/// to the output path given for it, see [`Backend::convolve_pipeline`].
+///
+/// Inputs and outputs may be [`STDIO`] to read from stdin or write to stdout.
+/// The output format is guessed from the output file extension unless given,
+/// and must be given when writing to stdout.
+///
+/// Everything happens in this process, so e.g. the GPU device and the
+/// thread pool are set up once for the whole batch.
+/// An image which fails does not stop the others from being processed.
+pub fn run(
+    jobs: impl IntoIterator<Item = (PathBuf, PathBuf)>,
+    kernels: &[KernelImpl],
+    backend: Backend,
+    options: Options,
+    format: Option<ImageFormat>,
+) -> Summary {
+    let mut summary = Summary::default();
+
+    for (input, output) in jobs {
+        info!(?input, ?output, "Processing");
+
+        match process(&input, &output, kernels, backend, options, format) {
+            Ok(()) => summary.succeeded.push(output),
+            Err(e) => {
+                error!(?input, %e, "Failed");
+                summary.failed.push((input, e));
+            }
+        }
+    }
+
+    info!(
+        succeeded = summary.succeeded.len(),
+        failed = summary.failed.len(),
+        "Batch done"
+    );
+
+    summary
+}
+
+fn process(
+    input: &Path,
+    output: &Path,
+    kernels: &[KernelImpl],
+    backend: Backend,
+    options: Options,
+    format: Option<ImageFormat>,
+) -> Result<()> {
+    let image = if is_stdio(input) {
+        let mut bytes = vec![];
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+        decode(&bytes)?
+    } else {
+        prepare(input)?
+    };
+
+    let image = image.convolve_pipeline(kernels.iter().cloned(), backend, options)?;
+
+    if is_stdio(output) {
+        let format = format.ok_or_else(|| {
+            Error::InvalidInput("an output format is needed when writing to stdout".into())
+        })?;
+
+        let mut stdout = std::io::stdout().lock();
+        write_to(&image, &mut stdout, format)?;
+        return Ok(stdout.flush()?);
+    }
+
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match format {
+        Some(format) => save_with_format(&image, output, format),
+        None => save(&image, output),
+    }
+}
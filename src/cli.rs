@@ -1,26 +1,297 @@
 use std::path::PathBuf;
 
+use crate::batch::{self, Output};
 use crate::convolution::Backend;
+use crate::kernel::KernelImpl;
 use crate::prelude::*;
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
+use image::ImageFormat;
 
 /// Image convolution program.
-/// The input image will be convolved and saved to the given output path.
+/// The input images will be convolved and saved to the given output path.
 #[derive(Parser, Debug)]
+#[command(group(ArgGroup::new("kernels").required(true).args(["kernel", "kernel_file"])))]
 pub struct Cli {
-    /// Path to input image
-    #[arg(short, long)]
-    pub input: PathBuf,
+    /// Path to input image, or `-` to read from stdin.
+    ///
+    /// May also be a directory of images or a glob pattern such as `'images/*.jpg'`.
+    /// Give several to process them all in a single run
+    #[arg(short, long, required = true, num_args = 1..)]
+    pub input: Vec<PathBuf>,
 
-    /// Path to output image
+    /// Path to output image, or `-` to write to stdout.
+    ///
+    /// When processing several images, either a directory to write them to
+    /// or a naming template such as `out/{stem}-{kernel}.{ext}`
     #[arg(short, long)]
     pub output: PathBuf,
 
-    /// Kernel to apply to image
-    #[arg(value_enum, short, long)]
-    pub kernel: Kernel,
+    /// Format of the output images, such as `png` or `jpg`.
+    /// Guessed from the output file extension if not given, but required when writing to stdout
+    #[arg(long, value_parser = parse_format)]
+    pub format: Option<ImageFormat>,
+
+    /// Kernel to apply to image.
+    ///
+    /// Repeat the argument or give a comma separated list, e.g. `gaussian-blur,sharpen`,
+    /// to apply several kernels one after the other
+    #[arg(value_enum, short, long, value_delimiter = ',')]
+    pub kernel: Vec<Kernel>,
+
+    /// Standard deviation of the Gaussian blur, for a blur of any size.
+    /// Applies to every `gaussian-blur` given by `--kernel`
+    #[arg(long, requires = "kernel", conflicts_with = "kernel_file")]
+    pub sigma: Option<f32>,
+
+    /// Radius of the Gaussian blur given by `--sigma`.
+    /// Defaults to three times sigma, rounded up, and may be at most 1024
+    #[arg(long, requires = "sigma", conflicts_with = "kernel_file")]
+    pub radius: Option<u32>,
+
+    /// Size of the box blur as `<width>x<height>`, or a single odd number for a square,
+    /// for a box blur of up to 4097x4097. Applies to every `box-blur` given by `--kernel`.
+    ///
+    /// Large box blurs are fastest with the `summed-area` backend
+    #[arg(
+        long,
+        value_parser = parse_size,
+        requires = "kernel",
+        conflicts_with = "kernel_file"
+    )]
+    pub box_size: Option<(u32, u32)>,
+
+    /// Apply each kernel this many times in a row,
+    /// e.g. three passes of a box blur come close to a Gaussian blur
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub passes: u32,
+
+    /// Path to a text file describing the kernel to apply to image.
+    /// Repeat the argument to apply several kernels one after the other.
+    ///
+    /// Rows of weights separated by whitespace or commas, optionally
+    /// along with `divisor <n>`, `normalization <n>` and `bias <n>` lines
+    #[arg(long)]
+    pub kernel_file: Vec<PathBuf>,
+
+    /// Merge all kernels into a single, larger kernel before convolving, see `KernelImpl::compose`.
+    ///
+    /// Only a single pass over the image is needed, but the result differs
+    /// from applying the kernels one after the other near the edges of the image.
+    /// Not supported by the `summed-area` backend, since merged kernels do not have equal weights
+    #[arg(long)]
+    pub merge_kernels: bool,
+
+    /// Convolve in strips of rows, keeping the working buffers of the backend within about this many MiB.
+    ///
+    /// The decoded input and the output are still held whole in the color type of the input,
+    /// so the peak memory is about twice the decoded image plus the budget.
+    /// The output is identical, but only the direct CPU backends are supported,
+    /// and only a single kernel and pass unless they are merged with `--merge-kernels`
+    #[arg(long, value_name = "MIB")]
+    pub memory_budget: Option<usize>,
 
     /// Backend to use for convolution
     #[arg(value_enum, short, long)]
     pub backend: Backend,
+
+    /// How to handle pixels past the edges of the image
+    #[arg(value_enum, long, default_value_t)]
+    pub border: BorderMode,
+
+    /// The color to use for the constant border mode, as comma separated RGB or RGBA channels in 0.0..=1.0
+    #[arg(long, value_parser = parse_color, default_value = "0,0,0,1")]
+    pub border_color: [f32; 4],
+
+    /// How to treat the alpha channel of images with transparency
+    #[arg(value_enum, long, default_value_t)]
+    pub alpha: AlphaMode,
+
+    /// Premultiply colors by alpha while convolving, which avoids fringes around transparent edges
+    #[arg(long)]
+    pub premultiply: bool,
+
+    /// The color space to convolve in
+    #[arg(value_enum, long, default_value_t)]
+    pub color_space: ColorSpace,
+}
+
+impl Cli {
+    /// Get the kernels to apply in order, either pre-defined ones or loaded from files,
+    /// each repeated for the number of `--passes`.
+    /// With `--merge-kernels` they are merged into a single kernel.
+    pub fn load_kernels(&self) -> Result<Vec<KernelImpl>> {
+        let kernels = self.parse_kernels()?;
+
+        // Merging e.g. the passes of a box blur gives a kernel with unequal weights.
+        let merges = kernels.len() > 1 || self.passes > 1;
+        if self.merge_kernels && merges && self.backend == Backend::SummedArea {
+            return Err(Error::InvalidKernel(
+                "--merge-kernels cannot be used with the summed area backend, \
+                 which needs kernels whose weights are all equal"
+                    .to_string(),
+            ));
+        }
+
+        let kernels = kernels
+            .into_iter()
+            .flat_map(|kernel| std::iter::repeat_n(kernel, self.passes as usize));
+
+        let kernels: Vec<KernelImpl> = if self.merge_kernels {
+            kernels
+                .reduce(|merged, kernel| merged.compose(&kernel))
+                .into_iter()
+                .collect()
+        } else {
+            kernels.collect()
+        };
+
+        // Checked up front rather than failing for every image, see `Backend::convolve_pipeline`.
+        if self.memory_budget.is_some() && kernels.len() > 1 {
+            return Err(Error::UnsupportedBackend(
+                "--memory-budget only supports a single kernel and pass, \
+                 unless they are merged with --merge-kernels"
+                    .to_string(),
+            ));
+        }
+
+        Ok(kernels)
+    }
+
+    fn parse_kernels(&self) -> Result<Vec<KernelImpl>> {
+        if !self.kernel_file.is_empty() {
+            return self.kernel_file.iter().map(KernelImpl::from_file).collect();
+        }
+
+        if self.sigma.is_some() && !self.kernel.contains(&Kernel::GaussianBlur) {
+            return Err(Error::InvalidKernel(
+                "--sigma only applies to the Gaussian blur kernel".to_string(),
+            ));
+        }
+
+        if self.box_size.is_some() && !self.kernel.contains(&Kernel::BoxBlur) {
+            return Err(Error::InvalidKernel(
+                "--box-size only applies to the box blur kernel".to_string(),
+            ));
+        }
+
+        self.kernel
+            .iter()
+            .map(|&kernel| match (kernel, self.sigma, self.box_size) {
+                (Kernel::GaussianBlur, Some(sigma), _) => KernelImpl::gaussian(sigma, self.radius),
+                (Kernel::BoxBlur, _, Some((width, height))) => KernelImpl::box_blur(width, height),
+                (kernel, _, _) => Ok(kernel.into()),
+            })
+            .collect()
+    }
+
+    /// Where to write the output images, see [`Output`].
+    ///
+    /// A single input file is written to the output path as is, unless
+    /// the output is an existing directory or a template.
+    pub fn output(&self) -> Output {
+        let output = self.output.to_string_lossy();
+
+        if batch::is_stdio(&self.output) {
+            Output::File(self.output.clone())
+        } else if output.contains('{') {
+            Output::Template(output.into_owned())
+        } else if self.output.is_dir() || !self.is_single_file() {
+            Output::Directory(self.output.clone())
+        } else {
+            Output::File(self.output.clone())
+        }
+    }
+
+    /// Whether a single input was given, which is neither a directory nor a pattern.
+    /// Stdin counts as a single file.
+    fn is_single_file(&self) -> bool {
+        match &self.input[..] {
+            [input] => !input.is_dir() && !batch::is_pattern(input),
+            _ => false,
+        }
+    }
+
+    /// A name for the kernels applied, for use in output names.
+    /// The kernel names or kernel file names joined by dashes, e.g. `gaussian-blur-sharpen`.
+    pub fn kernel_name(&self) -> String {
+        let names: Vec<String> = if self.kernel_file.is_empty() {
+            self.kernel
+                .iter()
+                .filter_map(|kernel| kernel.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect()
+        } else {
+            self.kernel_file
+                .iter()
+                .map(|path| {
+                    path.file_stem()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into()
+                })
+                .collect()
+        };
+
+        names.join("-")
+    }
+
+    /// Get the convolution options.
+    pub fn options(&self) -> Options {
+        Options {
+            border: self.border,
+            border_color: self.border_color,
+            alpha: self.alpha,
+            premultiply: self.premultiply,
+            color_space: self.color_space,
+            memory_budget: self.memory_budget.map(|mib| mib * 1024 * 1024),
+        }
+    }
+}
+
+/// Parses an image format from its file extension.
+fn parse_format(s: &str) -> std::result::Result<ImageFormat, String> {
+    ImageFormat::from_extension(s).ok_or_else(|| format!("unknown image format `{s}`"))
+}
+
+/// Parses a size such as `25x15`, or `25` for a square, of at most [`KernelImpl::MAX_BOX_SIZE`].
+fn parse_size(s: &str) -> std::result::Result<(u32, u32), String> {
+    let parse = |size: &str| {
+        let size = size
+            .trim()
+            .parse::<u32>()
+            .map_err(|e| format!("`{size}`: {e}"))?;
+
+        if size > KernelImpl::MAX_BOX_SIZE {
+            return Err(format!(
+                "`{size}`: must be at most {}",
+                KernelImpl::MAX_BOX_SIZE
+            ));
+        }
+
+        Ok(size)
+    };
+
+    match s.split_once('x') {
+        Some((width, height)) => Ok((parse(width)?, parse(height)?)),
+        None => parse(s).map(|size| (size, size)),
+    }
+}
+
+/// Parses RGB or RGBA channels, where alpha defaults to opaque.
+fn parse_color(s: &str) -> std::result::Result<[f32; 4], String> {
+    let channels = s
+        .split(',')
+        .map(|channel| {
+            channel
+                .trim()
+                .parse::<f32>()
+                .map_err(|e| format!("`{channel}`: {e}"))
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+
+    match channels[..] {
+        [red, green, blue] => Ok([red, green, blue, 1.]),
+        [red, green, blue, alpha] => Ok([red, green, blue, alpha]),
+        _ => Err(format!("expected 3 or 4 channels, got {}", channels.len())),
+    }
 }
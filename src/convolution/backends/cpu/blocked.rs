@@ -0,0 +1,232 @@
+use image::{DynamicImage, Pixel};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::simd::InstructionSet;
+use super::util::{Image, ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// How many bytes of input each block should read, including its halo.
+/// About the size of a typical L2 cache, leaving room for the output.
+const BLOCK_BYTES: usize = 128 * 1024;
+
+/// The smallest block width and height, such that blocks do not degenerate to
+/// mostly halo for large kernels.
+const MIN_BLOCK_SIZE: u32 = 16;
+
+/// The block size whose input, including the halo of the kernel radius on each side,
+/// fits in [`BLOCK_BYTES`].
+fn block_size(kernel: &KernelImpl) -> (u32, u32) {
+    let pixels = BLOCK_BYTES / std::mem::size_of::<ImagePixel>();
+    let side = (pixels as f64).sqrt() as u32;
+
+    (
+        side.saturating_sub(kernel.width() - 1).max(MIN_BLOCK_SIZE),
+        side.saturating_sub(kernel.height() - 1).max(MIN_BLOCK_SIZE),
+    )
+}
+
+/// Buffers and settings shared by [`Single`] and [`Multi`].
+struct BlockedBuffers {
+    buffers: ImageBuffers,
+    kernel: KernelImpl,
+    block_size: (u32, u32),
+    instruction_set: InstructionSet,
+}
+
+impl BlockedBuffers {
+    fn new(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        Self {
+            block_size: block_size(&kernel),
+            buffers,
+            kernel,
+            instruction_set: InstructionSet::detect(),
+        }
+    }
+
+    fn with_block_size(self, width: u32, height: u32) -> Self {
+        Self {
+            block_size: (width.max(1), height.max(1)),
+            ..self
+        }
+    }
+
+    /// Take the output buffer, which is convolved in bands of rows one block high,
+    /// see [`BlockedBuffers::convolve_band`].
+    /// Returns the buffer, and the length and height of each band.
+    fn take_output(&mut self) -> (Image, usize, usize) {
+        let output = std::mem::take(&mut self.buffers.output);
+        let (_, block_height) = self.block_size;
+        let band_len = output.width() as usize * CHANNELS * block_height as usize;
+
+        (output, band_len.max(1), block_height as usize)
+    }
+
+    /// Convolve the band of output rows starting at row `top`, one block at a time.
+    ///
+    /// Within a block, each output row is computed like [`super::simd::Simd`] does,
+    /// but only as wide as the block, such that the input rows it reads stay in the cache
+    /// for the following rows of the block.
+    fn convolve_band(&self, output_width: u32, top: usize, band: &mut [f32]) {
+        let kernel = &self.kernel;
+        let (block_width, _) = self.block_size;
+        let input = &*self.buffers.input;
+        let input_row_len = self.buffers.input.width() as usize * CHANNELS;
+        let output_row_len = output_width as usize * CHANNELS;
+        let block_len = block_width as usize * CHANNELS;
+
+        for left in (0..output_row_len).step_by(block_len) {
+            let right = (left + block_len).min(output_row_len);
+
+            for (y, output) in band.chunks_exact_mut(output_row_len).enumerate() {
+                let output = &mut output[left..right];
+                output.fill(0.);
+
+                for row in 0..kernel.height() {
+                    let start = (top + y + row as usize) * input_row_len + left;
+
+                    for col in 0..kernel.width() {
+                        let start = start + col as usize * CHANNELS;
+                        self.instruction_set.multiply_add(
+                            output,
+                            &input[start..start + output.len()],
+                            kernel.weight(col, row),
+                        );
+                    }
+                }
+
+                for channel in output {
+                    *channel = *channel * kernel.normalization() + kernel.bias();
+                }
+            }
+        }
+    }
+}
+
+/// Runs through the image in cache sized 2D blocks on a single thread.
+///
+/// The direct backends go through whole rows, so for large kernels each of the
+/// input rows under the kernel is read from main memory again for every output row.
+/// Blocks instead only read the input under the block plus its halo, the kernel radius
+/// on each side, which stays in the cache while the block is being convolved.
+///
+/// The results are identical to [`super::single::NestedLoops`].
+pub struct Single {
+    buffers: BlockedBuffers,
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Single {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Single {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+impl Single {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        Self {
+            buffers: BlockedBuffers::new(buffers, kernel),
+        }
+    }
+
+    /// Set the size of the blocks in output pixels, at least one in each dimension,
+    /// instead of fitting them in a typical L2 cache.
+    pub fn with_block_size(self, width: u32, height: u32) -> Self {
+        Self {
+            buffers: self.buffers.with_block_size(width, height),
+        }
+    }
+}
+
+impl ConvolveStrategy for Single {
+    fn convolve(&mut self) -> Result<()> {
+        let (mut output, band_len, band_height) = self.buffers.take_output();
+        let width = output.width();
+
+        output
+            .chunks_mut(band_len)
+            .enumerate()
+            .for_each(|(band, rows)| self.buffers.convolve_band(width, band * band_height, rows));
+
+        self.buffers.buffers.output = output;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.buffers.finish())
+    }
+}
+
+/// Like [`Single`], but the bands of blocks are convolved in parallel.
+#[cfg(feature = "rayon")]
+pub struct Multi {
+    buffers: BlockedBuffers,
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Multi {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Multi {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Multi {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        Self {
+            buffers: BlockedBuffers::new(buffers, kernel),
+        }
+    }
+
+    /// Set the size of the blocks in output pixels, at least one in each dimension,
+    /// instead of fitting them in a typical L2 cache.
+    pub fn with_block_size(self, width: u32, height: u32) -> Self {
+        Self {
+            buffers: self.buffers.with_block_size(width, height),
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl ConvolveStrategy for Multi {
+    fn convolve(&mut self) -> Result<()> {
+        let (mut output, band_len, band_height) = self.buffers.take_output();
+        let width = output.width();
+
+        output
+            .par_chunks_mut(band_len)
+            .enumerate()
+            .for_each(|(band, rows)| self.buffers.convolve_band(width, band * band_height, rows));
+
+        self.buffers.buffers.output = output;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.buffers.finish())
+    }
+}
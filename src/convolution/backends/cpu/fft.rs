@@ -0,0 +1,245 @@
+use std::sync::Arc;
+
+use image::{DynamicImage, Pixel};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::util::{ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// A rough estimate of how many kernel weights a direct convolution can apply
+/// per pixel in the time the FFT spends per pixel, for each doubling of the image size.
+/// Used by [`is_preferable`].
+const FFT_COST_PER_DOUBLING: f32 = 8.;
+
+/// Whether convolving an image of the given size with the given kernel
+/// is expected to be faster using [`Fft`] than a direct convolution.
+///
+/// A direct convolution costs `width * height` of the kernel per pixel,
+/// while the FFT costs `log2(pixels)` per pixel regardless of the kernel.
+pub fn is_preferable(kernel: &KernelImpl, (width, height): (u32, u32)) -> bool {
+    let direct = (kernel.width() * kernel.height()) as f32;
+    let fft = FFT_COST_PER_DOUBLING * ((width as f32 * height as f32).max(2.)).log2();
+
+    direct > fft
+}
+
+/// Convolution via the fast Fourier transform.
+///
+/// The padded input and the kernel are transformed to the frequency domain, where
+/// convolution is a pointwise multiplication, and then transformed back.
+/// The cost does not depend on the kernel size, which makes this the fastest backend for large kernels.
+/// See [`is_preferable`].
+///
+/// Since the input is padded according to the border mode just like the spatial backends,
+/// the results match theirs up to floating point rounding.
+pub struct Fft {
+    buffers: ImageBuffers,
+    kernel: KernelImpl,
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Fft {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Fft {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
+        Self {
+            buffers: ImageBuffers::new(input, &kernel, &options),
+            kernel,
+        }
+    }
+}
+
+/// Transforms between the spatial and frequency domain in two dimensions.
+///
+/// The frequency domain data is kept transposed, which saves
+/// transposing back and forth between forward and inverse transforms.
+struct Fft2d {
+    width: usize,
+    height: usize,
+    row_forward: Arc<dyn rustfft::Fft<f32>>,
+    row_inverse: Arc<dyn rustfft::Fft<f32>>,
+    column_forward: Arc<dyn rustfft::Fft<f32>>,
+    column_inverse: Arc<dyn rustfft::Fft<f32>>,
+}
+
+impl Fft2d {
+    fn new(width: usize, height: usize) -> Self {
+        let mut planner = FftPlanner::new();
+
+        Self {
+            width,
+            height,
+            row_forward: planner.plan_fft_forward(width),
+            row_inverse: planner.plan_fft_inverse(width),
+            column_forward: planner.plan_fft_forward(height),
+            column_inverse: planner.plan_fft_inverse(height),
+        }
+    }
+
+    /// Transform `height` rows of `width` values into the (transposed) frequency domain.
+    fn forward(&self, data: &mut Vec<Complex<f32>>) {
+        process_rows(&self.row_forward, data);
+        *data = transpose(data, self.width, self.height);
+        process_rows(&self.column_forward, data);
+    }
+
+    /// Transform (transposed) frequency domain data back to `height` rows of `width` values.
+    /// The result is not normalized, i.e. it is scaled by `width * height`.
+    fn inverse(&self, data: &mut Vec<Complex<f32>>) {
+        process_rows(&self.column_inverse, data);
+        *data = transpose(data, self.height, self.width);
+        process_rows(&self.row_inverse, data);
+    }
+}
+
+/// Runs the FFT on each row of the data in parallel.
+#[cfg(feature = "rayon")]
+fn process_rows(fft: &Arc<dyn rustfft::Fft<f32>>, data: &mut [Complex<f32>]) {
+    data.par_chunks_exact_mut(fft.len()).for_each_init(
+        || vec![Complex::default(); fft.get_inplace_scratch_len()],
+        |scratch, row| fft.process_with_scratch(row, scratch),
+    );
+}
+
+/// Runs the FFT on each row of the data.
+#[cfg(not(feature = "rayon"))]
+fn process_rows(fft: &Arc<dyn rustfft::Fft<f32>>, data: &mut [Complex<f32>]) {
+    let mut scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+
+    for row in data.chunks_exact_mut(fft.len()) {
+        fft.process_with_scratch(row, &mut scratch);
+    }
+}
+
+/// Transposes `height` rows of `width` values into `width` rows of `height` values.
+fn transpose(data: &[Complex<f32>], width: usize, height: usize) -> Vec<Complex<f32>> {
+    let mut transposed = vec![Complex::default(); data.len()];
+
+    #[cfg(feature = "rayon")]
+    let columns = transposed.par_chunks_exact_mut(height);
+    #[cfg(not(feature = "rayon"))]
+    let columns = transposed.chunks_exact_mut(height);
+
+    columns.enumerate().for_each(|(x, column)| {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[x + y * width];
+        }
+    });
+
+    transposed
+}
+
+/// The smallest length at least `len` which only has factors 2, 3 and 5,
+/// since the FFT is fastest for such lengths.
+fn fast_len(len: usize) -> usize {
+    (len..)
+        .find(|&candidate| {
+            let mut remaining = candidate;
+            for factor in [2, 3, 5] {
+                while remaining % factor == 0 {
+                    remaining /= factor;
+                }
+            }
+            remaining == 1
+        })
+        .expect("there is always a larger 5-smooth number")
+}
+
+impl ConvolveStrategy for Fft {
+    fn convolve(&mut self) -> Result<()> {
+        let input = &self.buffers.input;
+        let output = &mut self.buffers.output;
+
+        // The transforms need non-empty lengths, and there is nothing to compute anyway.
+        if output.is_empty() {
+            return Ok(());
+        }
+
+        let (input_width, input_height) = input.dimensions();
+        let (kernel_width, kernel_height) = (self.kernel.width(), self.kernel.height());
+
+        // Circular convolution wraps around, but since only output pixels where the kernel is fully
+        // within the padded input are kept, no extra padding is needed to avoid wrapped values.
+        let (width, height) = (
+            fast_len(input_width as usize),
+            fast_len(input_height as usize),
+        );
+        let fft = Fft2d::new(width, height);
+
+        // The backends compute a correlation (the kernel is not flipped),
+        // which is the same as a convolution with a flipped kernel.
+        let mut kernel = vec![Complex::default(); width * height];
+        for row in 0..kernel_height {
+            for col in 0..kernel_width {
+                kernel[col as usize + row as usize * width] = Complex::from(
+                    self.kernel
+                        .weight(kernel_width - 1 - col, kernel_height - 1 - row),
+                );
+            }
+        }
+        fft.forward(&mut kernel);
+
+        let scale = self.kernel.normalization() / (width * height) as f32;
+        let bias = self.kernel.bias();
+
+        // The kernel is real, so two channels can be transformed at once
+        // by using the real and imaginary parts.
+        let channel_pairs = (0..CHANNELS)
+            .step_by(2)
+            .map(|channel| (channel, (channel + 1 < CHANNELS).then_some(channel + 1)));
+
+        for (first, second) in channel_pairs {
+            let mut data = vec![Complex::default(); width * height];
+            for (x, y, pixel) in input.enumerate_pixels() {
+                let channels = pixel.channels();
+                data[x as usize + y as usize * width] = Complex::new(
+                    channels[first],
+                    second.map_or(0., |second| channels[second]),
+                );
+            }
+
+            fft.forward(&mut data);
+
+            #[cfg(feature = "rayon")]
+            let values = data.par_iter_mut();
+            #[cfg(not(feature = "rayon"))]
+            let values = data.iter_mut();
+
+            values
+                .zip(&kernel)
+                .for_each(|(value, kernel)| *value *= kernel);
+            fft.inverse(&mut data);
+
+            // The full convolution at (x + kernel_width - 1, y + kernel_height - 1)
+            // is the output at (x, y), see [`ImageBuffers`].
+            for (x, y, pixel) in output.enumerate_pixels_mut() {
+                let index =
+                    (x + kernel_width - 1) as usize + (y + kernel_height - 1) as usize * width;
+                let value = data[index] * scale;
+
+                let channels = pixel.channels_mut();
+                channels[first] = value.re + bias;
+                if let Some(second) = second {
+                    channels[second] = value.im + bias;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.finish())
+    }
+}
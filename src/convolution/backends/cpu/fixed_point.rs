@@ -0,0 +1,271 @@
+use image::{ColorType, DynamicImage, GenericImageView, Rgba, RgbaImage};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+const CHANNELS: usize = 4;
+
+/// How the weighted sums are scaled down by the normalization.
+#[derive(Debug, Clone, Copy)]
+enum Normalization {
+    /// Divide by `2^shift`.
+    Shift(u32),
+
+    /// Divide by any other positive integer.
+    Divide(i32),
+}
+
+impl Normalization {
+    /// Apply to a weighted sum, rounding to the nearest integer.
+    #[inline(always)]
+    fn apply(self, sum: i32) -> i32 {
+        match self {
+            Normalization::Shift(0) => sum,
+            Normalization::Shift(shift) => (sum + (1 << (shift - 1))) >> shift,
+            Normalization::Divide(divisor) => (sum + divisor / 2).div_euclid(divisor),
+        }
+    }
+}
+
+/// A kernel with integer weights, and a normalization which divides by an integer.
+struct IntegerKernel {
+    width: u32,
+    height: u32,
+    weights: Vec<i32>,
+    normalization: Normalization,
+
+    /// The bias in 8 bit units, multiplied by the divisor, such that
+    /// it is added to the weighted sums before normalizing.
+    bias: i32,
+}
+
+impl TryFrom<&KernelImpl> for IntegerKernel {
+    type Error = Error;
+
+    fn try_from(kernel: &KernelImpl) -> Result<Self> {
+        if kernel.weights().iter().any(|weight| weight.fract() != 0.) {
+            return Err(Error::UnsupportedKernel(
+                "the fixed point backend needs integer weights".into(),
+            ));
+        }
+
+        // E.g. 0.111_111_11 for a 3x3 box blur is 1/9 up to rounding.
+        let divisor = (1. / kernel.normalization() as f64).round();
+        let exact = (1. / divisor - kernel.normalization() as f64).abs()
+            <= kernel.normalization().abs() as f64 * 1e-6;
+        if !(divisor >= 1. && divisor <= i32::MAX as f64 && exact) {
+            return Err(Error::UnsupportedKernel(format!(
+                "the fixed point backend needs the normalization to be one over an integer, got {}",
+                kernel.normalization()
+            )));
+        }
+
+        // The sum of the weighted channels must fit in an i32 in the worst case.
+        let total: f64 = kernel
+            .weights()
+            .iter()
+            .map(|weight| weight.abs() as f64)
+            .sum();
+        let bias = (kernel.bias() as f64 * u8::MAX as f64 * divisor).round();
+        if total * u8::MAX as f64 + bias.abs() + divisor > i32::MAX as f64 {
+            return Err(Error::UnsupportedKernel(
+                "the kernel weights are too large for the fixed point backend".into(),
+            ));
+        }
+
+        let divisor = divisor as i32;
+        let normalization = if (divisor as u32).is_power_of_two() {
+            Normalization::Shift(divisor.trailing_zeros())
+        } else {
+            Normalization::Divide(divisor)
+        };
+
+        Ok(Self {
+            width: kernel.width(),
+            height: kernel.height(),
+            weights: kernel
+                .weights()
+                .iter()
+                .map(|&weight| weight as i32)
+                .collect(),
+            normalization,
+            bias: bias as i32,
+        })
+    }
+}
+
+/// Convolution of 8 bit images in integer arithmetic.
+///
+/// The pixels stay 8 bit throughout, which is a quarter of the memory traffic of
+/// the floating point backends. Each channel is accumulated in an `i32`, then divided by
+/// the normalization with rounding, and saturated to `0..=255`.
+/// Runs in parallel at the row level with the `rayon` feature.
+///
+/// Only supports:
+/// * 8 bit images, i.e. L8, La8, Rgb8 and Rgba8
+/// * Kernels with integer weights and a normalization of one over an integer,
+///   such as all the [`Kernel`] presets
+/// * [`ColorSpace::Srgb`] and no premultiplication, since both need more than 8 bits per channel
+///
+/// The results are within one of the floating point backends, except near the edges with
+/// a [`BorderMode::Constant`] color which is not a multiple of `1/255`, since it is rounded first.
+///
+/// Created from an `(image, kernel)` pair, the default options are used but with [`ColorSpace::Srgb`].
+pub struct FixedPoint {
+    input: RgbaImage,
+    output: RgbaImage,
+    kernel: IntegerKernel,
+    options: Options,
+    color_type: ColorType,
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K)> for FixedPoint {
+    type Error = Error;
+
+    fn try_from((input, kernel): (DynamicImage, K)) -> Result<Self> {
+        Self::try_from((
+            input,
+            kernel,
+            Options {
+                color_space: ColorSpace::Srgb,
+                ..Options::default()
+            },
+        ))
+    }
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K, Options)> for FixedPoint {
+    type Error = Error;
+
+    fn try_from((input, kernel, options): (DynamicImage, K, Options)) -> Result<Self> {
+        let kernel = kernel.into();
+        let color_type = input.color();
+
+        if !matches!(
+            color_type,
+            ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+        ) {
+            return Err(Error::UnsupportedBackend(format!(
+                "the fixed point backend only supports 8 bit images, got {color_type:?}"
+            )));
+        }
+
+        if options.color_space != ColorSpace::Srgb || options.premultiply {
+            return Err(Error::UnsupportedBackend(
+                "the fixed point backend only supports the sRGB color space without premultiplication"
+                    .into(),
+            ));
+        }
+
+        let (width, height) = options.output_dimensions(input.dimensions(), &kernel);
+
+        Ok(Self {
+            input: input.into_rgba8(),
+            output: RgbaImage::new(width, height),
+            kernel: IntegerKernel::try_from(&kernel)?,
+            options,
+            color_type,
+        })
+    }
+}
+
+/// The 8 bit value of a channel in `0.0..=1.0`.
+fn to_u8(channel: f32) -> u8 {
+    (channel.clamp(0., 1.) * u8::MAX as f32).round() as u8
+}
+
+impl FixedPoint {
+    /// Pads the input by the kernel radius on each side as described by the border mode,
+    /// such that the kernel centered on output pixel `(x, y)` covers the padded pixels
+    /// starting at `(x, y)`. Cropping requires no padding.
+    fn pad(&self) -> RgbaImage {
+        let (radius_x, radius_y) = match self.options.border {
+            BorderMode::Crop => (0, 0),
+            _ => (self.kernel.width / 2, self.kernel.height / 2),
+        };
+        let (width, height) = self.input.dimensions();
+        let border_color = Rgba(self.options.border_color.map(to_u8));
+
+        RgbaImage::from_fn(width + 2 * radius_x, height + 2 * radius_y, |x, y| {
+            let border = self.options.border;
+
+            match (
+                border.source_index(x as i64 - radius_x as i64, width),
+                border.source_index(y as i64 - radius_y as i64, height),
+            ) {
+                (Some(x), Some(y)) => *self.input.get_pixel(x, y),
+                _ => border_color,
+            }
+        })
+    }
+}
+
+/// Convolve output row `y`, writing the saturated results.
+#[inline(always)]
+fn convolve_row(kernel: &IntegerKernel, padded: &RgbaImage, y: usize, output: &mut [u8]) {
+    let padded_row_len = padded.width() as usize * CHANNELS;
+    let padded = padded.as_raw();
+
+    for (x, pixel) in output.chunks_exact_mut(CHANNELS).enumerate() {
+        let mut sums = [kernel.bias; CHANNELS];
+
+        for row in 0..kernel.height as usize {
+            let start = (y + row) * padded_row_len + x * CHANNELS;
+            let weights = &kernel.weights[row * kernel.width as usize..][..kernel.width as usize];
+            let inputs = &padded[start..start + weights.len() * CHANNELS];
+
+            for (weight, input) in weights.iter().zip(inputs.chunks_exact(CHANNELS)) {
+                for (sum, &channel) in sums.iter_mut().zip(input) {
+                    *sum += weight * channel as i32;
+                }
+            }
+        }
+
+        for (channel, sum) in pixel.iter_mut().zip(sums) {
+            *channel = kernel.normalization.apply(sum).clamp(0, u8::MAX as i32) as u8;
+        }
+    }
+}
+
+impl ConvolveStrategy for FixedPoint {
+    fn convolve(&mut self) -> Result<()> {
+        let padded = self.pad();
+        let kernel = &self.kernel;
+        let row_len = (self.output.width() as usize * CHANNELS).max(1);
+
+        #[cfg(feature = "rayon")]
+        let rows = self.output.par_chunks_exact_mut(row_len);
+        #[cfg(not(feature = "rayon"))]
+        let rows = self.output.chunks_exact_mut(row_len);
+
+        rows.enumerate()
+            .for_each(|(y, output)| convolve_row(kernel, &padded, y, output));
+
+        if self.options.alpha == AlphaMode::PassThrough {
+            let (radius_x, radius_y) = match self.options.border {
+                BorderMode::Crop => (kernel.width / 2, kernel.height / 2),
+                _ => (0, 0),
+            };
+
+            // The input pixel the kernel was centered on.
+            for (x, y, pixel) in self.output.enumerate_pixels_mut() {
+                pixel[3] = self.input.get_pixel(x + radius_x, y + radius_y)[3];
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        let output = DynamicImage::ImageRgba8(self.output);
+
+        Ok(match self.color_type {
+            ColorType::L8 => output.to_luma8().into(),
+            ColorType::La8 => output.to_luma_alpha8().into(),
+            ColorType::Rgb8 => output.to_rgb8().into(),
+            _ => output,
+        })
+    }
+}
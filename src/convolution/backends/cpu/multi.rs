@@ -1,10 +1,18 @@
-use image::DynamicImage;
-use rayon::prelude::*;
+use std::sync::Arc;
+
+use image::{DynamicImage, Pixel};
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
 
 use crate::kernel::KernelImpl;
 use crate::prelude::*;
 
-use super::util::{do_convolve, view3x3, ImageBuffers};
+use super::util::{do_convolve, view_kernel, ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// How many bands each thread gets by default, such that threads which finish
+/// early can take over work from the others.
+const BANDS_PER_THREAD: usize = 4;
 
 /// Uses nested iterators, but runs in parallel at the row level.
 pub struct NestedIterators {
@@ -12,38 +20,170 @@ pub struct NestedIterators {
     kernel: KernelImpl,
 }
 
-impl From<(DynamicImage, Kernel)> for NestedIterators {
-    fn from((input, kernel): (DynamicImage, Kernel)) -> Self {
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for NestedIterators {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for NestedIterators {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
         Self {
-            buffers: ImageBuffers::new(input),
-            kernel: kernel.into(),
+            buffers: ImageBuffers::new(input, &kernel, &options),
+            kernel,
         }
     }
 }
 
+impl NestedIterators {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        Self { buffers, kernel }
+    }
+}
+
 impl ConvolveStrategy for NestedIterators {
     fn convolve(&mut self) -> Result<()> {
-        let (width, height) = self.buffers.dimensions();
-
         self.buffers
             .output
             .enumerate_rows_mut()
-            .take(height - 1)
-            .skip(1)
             .par_bridge()
             .for_each(|(_, row_iter)| {
-                row_iter
-                    .take(width - 1)
-                    .skip(1)
-                    .for_each(|(col, row, pixel)| {
-                        do_convolve(self.kernel, pixel, &*view3x3(&self.buffers.input, row, col))
-                    })
+                row_iter.for_each(|(col, row, pixel)| {
+                    do_convolve(
+                        &self.kernel,
+                        pixel,
+                        &*view_kernel(&self.buffers.input, &self.kernel, row, col),
+                    )
+                })
             });
 
         Ok(())
     }
 
     fn finish(self) -> Result<DynamicImage> {
-        Ok(self.buffers.output.into())
+        Ok(self.buffers.finish())
+    }
+}
+
+/// Splits the output into bands of contiguous rows, which are convolved in parallel.
+///
+/// Unlike [`NestedIterators`], which hands out single rows through a shared iterator,
+/// each thread gets whole bands up front via `par_chunks_mut`, and works through
+/// neighbouring rows which share most of their input.
+///
+/// By default there are a few bands per thread of the global rayon thread pool,
+/// see [`Chunked::with_band_height`] and [`Chunked::with_threads`] to change this.
+pub struct Chunked {
+    buffers: ImageBuffers,
+    kernel: KernelImpl,
+    band_height: Option<u32>,
+    thread_pool: Option<Arc<ThreadPool>>,
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Chunked {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Chunked {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+impl Chunked {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        Self {
+            buffers,
+            kernel,
+            band_height: None,
+            thread_pool: None,
+        }
+    }
+
+    /// Set the number of rows in each band, at least one.
+    pub fn with_band_height(self, rows: u32) -> Self {
+        Self {
+            band_height: Some(rows.max(1)),
+            ..self
+        }
+    }
+
+    /// Run on a new thread pool with the given number of threads,
+    /// instead of the global rayon thread pool.
+    ///
+    /// # Errors
+    ///
+    /// If the thread pool cannot be created.
+    pub fn with_threads(self, threads: usize) -> Result<Self> {
+        let thread_pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+
+        Ok(self.with_thread_pool(Arc::new(thread_pool)))
+    }
+
+    /// Run on the given thread pool, instead of the global rayon thread pool.
+    pub fn with_thread_pool(self, thread_pool: Arc<ThreadPool>) -> Self {
+        Self {
+            thread_pool: Some(thread_pool),
+            ..self
+        }
+    }
+
+    /// Convolve all bands, on the current thread pool.
+    fn convolve_bands(&mut self) {
+        let width = self.buffers.output.width();
+        let height = self.buffers.output.height() as usize;
+        let band_height = self
+            .band_height
+            .map_or_else(
+                || height.div_ceil(rayon::current_num_threads() * BANDS_PER_THREAD),
+                |rows| rows as usize,
+            )
+            .max(1);
+
+        let row_len = width as usize * CHANNELS;
+        let (input, kernel) = (&self.buffers.input, &self.kernel);
+
+        self.buffers
+            .output
+            .par_chunks_mut((row_len * band_height).max(1))
+            .enumerate()
+            .for_each(|(band, output)| {
+                let top = band * band_height;
+
+                for (y, output) in output.chunks_exact_mut(row_len).enumerate() {
+                    let row = (top + y) as u32;
+
+                    for (col, pixel) in output.chunks_exact_mut(CHANNELS).enumerate() {
+                        do_convolve(
+                            kernel,
+                            ImagePixel::from_slice_mut(pixel),
+                            &*view_kernel(input, kernel, row, col as u32),
+                        );
+                    }
+                }
+            });
+    }
+}
+
+impl ConvolveStrategy for Chunked {
+    fn convolve(&mut self) -> Result<()> {
+        match self.thread_pool.clone() {
+            Some(thread_pool) => thread_pool.install(|| self.convolve_bands()),
+            None => self.convolve_bands(),
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.finish())
     }
 }
@@ -0,0 +1,239 @@
+use image::{DynamicImage, Pixel};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::util::{Image, ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// The parts of a separable kernel, see [`KernelImpl::separate`].
+struct SeparatedKernel {
+    horizontal: Vec<f32>,
+    vertical: Vec<f32>,
+    normalization: f32,
+    bias: f32,
+}
+
+impl TryFrom<KernelImpl> for SeparatedKernel {
+    type Error = Error;
+
+    fn try_from(kernel: KernelImpl) -> Result<Self> {
+        let (horizontal, vertical) = kernel.separate().ok_or_else(|| {
+            Error::UnsupportedKernel(format!(
+                "the {}x{} kernel is not separable",
+                kernel.width(),
+                kernel.height()
+            ))
+        })?;
+
+        Ok(Self {
+            horizontal,
+            vertical,
+            normalization: kernel.normalization(),
+            bias: kernel.bias(),
+        })
+    }
+}
+
+/// Buffers for a two-pass convolution.
+/// The horizontal pass reads the padded input and writes the intermediate buffer,
+/// which has the width of the output and the height of the padded input.
+/// The vertical pass reads the intermediate buffer and writes the output.
+struct SeparableBuffers {
+    buffers: ImageBuffers,
+    intermediate: Image,
+}
+
+impl SeparableBuffers {
+    fn new(buffers: ImageBuffers) -> Self {
+        let intermediate = Image::new(buffers.output.width(), buffers.input.height());
+
+        Self {
+            buffers,
+            intermediate,
+        }
+    }
+
+    fn row_len(image: &Image) -> usize {
+        // An empty image has no rows, but the chunk size must not be zero.
+        (image.width() as usize * CHANNELS).max(1)
+    }
+}
+
+/// Convolve a single row horizontally.
+#[inline(always)]
+fn horizontal_row(weights: &[f32], input: &[f32], output: &mut [f32]) {
+    for (x, pixel) in output.chunks_exact_mut(CHANNELS).enumerate() {
+        pixel.fill(0.);
+
+        for (k, weight) in weights.iter().enumerate() {
+            let start = (x + k) * CHANNELS;
+            let source = &input[start..start + CHANNELS];
+
+            for (output_channel, input_channel) in pixel.iter_mut().zip(source) {
+                *output_channel += input_channel * weight;
+            }
+        }
+    }
+}
+
+/// Convolve output row `y` vertically, applying normalization and bias.
+#[inline(always)]
+fn vertical_row(kernel: &SeparatedKernel, intermediate: &[f32], y: usize, output: &mut [f32]) {
+    let row_len = output.len();
+    output.fill(0.);
+
+    for (k, weight) in kernel.vertical.iter().enumerate() {
+        let start = (y + k) * row_len;
+        let source = &intermediate[start..start + row_len];
+
+        for (output_channel, input_channel) in output.iter_mut().zip(source) {
+            *output_channel += input_channel * weight;
+        }
+    }
+
+    for channel in output {
+        *channel = *channel * kernel.normalization + kernel.bias;
+    }
+}
+
+/// Runs a horizontal and then a vertical 1D pass on a single thread.
+/// Only supports separable kernels, see [`KernelImpl::separate`].
+pub struct Single {
+    buffers: SeparableBuffers,
+    kernel: SeparatedKernel,
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K)> for Single {
+    type Error = Error;
+
+    fn try_from((input, kernel): (DynamicImage, K)) -> Result<Self> {
+        Self::try_from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K, Options)> for Single {
+    type Error = Error;
+
+    fn try_from((input, kernel, options): (DynamicImage, K, Options)) -> Result<Self> {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+impl Single {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Result<Self> {
+        Ok(Self {
+            buffers: SeparableBuffers::new(buffers),
+            kernel: kernel.try_into()?,
+        })
+    }
+}
+
+impl ConvolveStrategy for Single {
+    fn convolve(&mut self) -> Result<()> {
+        let SeparableBuffers {
+            buffers,
+            intermediate,
+        } = &mut self.buffers;
+
+        let input_row_len = SeparableBuffers::row_len(&buffers.input);
+        let intermediate_row_len = SeparableBuffers::row_len(intermediate);
+        let output_row_len = SeparableBuffers::row_len(&buffers.output);
+
+        buffers
+            .input
+            .chunks_exact(input_row_len)
+            .zip(intermediate.chunks_exact_mut(intermediate_row_len))
+            .for_each(|(input, output)| horizontal_row(&self.kernel.horizontal, input, output));
+
+        buffers
+            .output
+            .chunks_exact_mut(output_row_len)
+            .enumerate()
+            .for_each(|(y, output)| vertical_row(&self.kernel, intermediate, y, output));
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.buffers.finish())
+    }
+}
+
+/// Runs a horizontal and then a vertical 1D pass, each parallel at the row level.
+/// Only supports separable kernels, see [`KernelImpl::separate`].
+#[cfg(feature = "rayon")]
+pub struct Multi {
+    buffers: SeparableBuffers,
+    kernel: SeparatedKernel,
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K)> for Multi {
+    type Error = Error;
+
+    fn try_from((input, kernel): (DynamicImage, K)) -> Result<Self> {
+        Self::try_from((input, kernel, Options::default()))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K, Options)> for Multi {
+    type Error = Error;
+
+    fn try_from((input, kernel, options): (DynamicImage, K, Options)) -> Result<Self> {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Multi {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Result<Self> {
+        Ok(Self {
+            buffers: SeparableBuffers::new(buffers),
+            kernel: kernel.try_into()?,
+        })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl ConvolveStrategy for Multi {
+    fn convolve(&mut self) -> Result<()> {
+        let SeparableBuffers {
+            buffers,
+            intermediate,
+        } = &mut self.buffers;
+
+        let input_row_len = SeparableBuffers::row_len(&buffers.input);
+        let intermediate_row_len = SeparableBuffers::row_len(intermediate);
+        let output_row_len = SeparableBuffers::row_len(&buffers.output);
+
+        buffers
+            .input
+            .par_chunks_exact(input_row_len)
+            .zip(intermediate.par_chunks_exact_mut(intermediate_row_len))
+            .for_each(|(input, output)| horizontal_row(&self.kernel.horizontal, input, output));
+
+        let intermediate = &*intermediate;
+        buffers
+            .output
+            .par_chunks_exact_mut(output_row_len)
+            .enumerate()
+            .for_each(|(y, output)| vertical_row(&self.kernel, intermediate, y, output));
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.buffers.finish())
+    }
+}
@@ -0,0 +1,228 @@
+#[cfg(target_arch = "x86")]
+use std::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use std::arch::x86_64::*;
+
+use image::{DynamicImage, Pixel};
+use tracing::debug;
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::util::{ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// The instructions used for the inner loop, detected at runtime.
+///
+/// Only public such that tests can check each of them, see [`Simd::with_instruction_set`].
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstructionSet {
+    /// 8 lanes.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Avx,
+
+    /// 4 lanes.
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Sse,
+
+    /// Plain loops, which the compiler may still vectorize.
+    Scalar,
+}
+
+impl InstructionSet {
+    /// Whether the CPU we are running on supports this instruction set.
+    pub fn is_supported(self) -> bool {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Avx => is_x86_feature_detected!("avx"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Sse => is_x86_feature_detected!("sse"),
+            Self::Scalar => true,
+        }
+    }
+
+    /// The widest instruction set supported by the CPU we are running on.
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx") {
+                return Self::Avx;
+            }
+            if is_x86_feature_detected!("sse") {
+                return Self::Sse;
+            }
+        }
+
+        Self::Scalar
+    }
+
+    /// Computes `output[i] += input[i] * weight`.
+    #[inline(always)]
+    pub(super) fn multiply_add(self, output: &mut [f32], input: &[f32], weight: f32) {
+        let len = output.len().min(input.len());
+        let (output, input) = (&mut output[..len], &input[..len]);
+
+        match self {
+            // Safety: The instruction sets were detected to be supported.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Avx => unsafe { multiply_add_avx(output, input, weight) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Sse => unsafe { multiply_add_sse(output, input, weight) },
+            Self::Scalar => multiply_add_scalar(output, input, weight),
+        }
+    }
+}
+
+#[inline(always)]
+fn multiply_add_scalar(output: &mut [f32], input: &[f32], weight: f32) {
+    for (output, input) in output.iter_mut().zip(input) {
+        *output += input * weight;
+    }
+}
+
+/// Safety: The CPU must support AVX, and the slices must have the same length.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx")]
+unsafe fn multiply_add_avx(output: &mut [f32], input: &[f32], weight: f32) {
+    const LANES: usize = 8;
+
+    let weights = _mm256_set1_ps(weight);
+    let mut outputs = output.chunks_exact_mut(LANES);
+    let mut inputs = input.chunks_exact(LANES);
+
+    for (output, input) in (&mut outputs).zip(&mut inputs) {
+        // Multiply and add separately instead of fused, which rounds like the scalar code.
+        let product = _mm256_mul_ps(_mm256_loadu_ps(input.as_ptr()), weights);
+        let sum = _mm256_add_ps(_mm256_loadu_ps(output.as_ptr()), product);
+        _mm256_storeu_ps(output.as_mut_ptr(), sum);
+    }
+
+    multiply_add_scalar(outputs.into_remainder(), inputs.remainder(), weight);
+}
+
+/// Safety: The CPU must support SSE, and the slices must have the same length.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "sse")]
+unsafe fn multiply_add_sse(output: &mut [f32], input: &[f32], weight: f32) {
+    const LANES: usize = 4;
+
+    let weights = _mm_set1_ps(weight);
+    let mut outputs = output.chunks_exact_mut(LANES);
+    let mut inputs = input.chunks_exact(LANES);
+
+    for (output, input) in (&mut outputs).zip(&mut inputs) {
+        let product = _mm_mul_ps(_mm_loadu_ps(input.as_ptr()), weights);
+        let sum = _mm_add_ps(_mm_loadu_ps(output.as_ptr()), product);
+        _mm_storeu_ps(output.as_mut_ptr(), sum);
+    }
+
+    multiply_add_scalar(outputs.into_remainder(), inputs.remainder(), weight);
+}
+
+/// A single threaded CPU convolution strategy using explicit SIMD instructions.
+///
+/// Works on whole rows of interleaved channels: for each weight, the input row shifted by
+/// the weight's column is multiplied by the weight and added to the output row.
+/// The channels of neighbouring pixels therefore fill the SIMD lanes, whatever the kernel size.
+///
+/// Uses AVX or SSE if the CPU supports them, detected at runtime, and plain loops otherwise.
+/// The weights are applied in the same order as [`super::single::NestedLoops`],
+/// without fused multiply-adds, so the results are identical.
+pub struct Simd {
+    buffers: ImageBuffers,
+    kernel: KernelImpl,
+    instruction_set: InstructionSet,
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Simd {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Simd {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+
+        Self::from_buffers(ImageBuffers::new(input, &kernel, &options), kernel)
+    }
+}
+
+impl Simd {
+    /// Convolve already prepared buffers, see [`super::tiled`].
+    pub(crate) fn from_buffers(buffers: ImageBuffers, kernel: KernelImpl) -> Self {
+        let instruction_set = InstructionSet::detect();
+        debug!(?instruction_set, "SIMD");
+
+        Self {
+            buffers,
+            kernel,
+            instruction_set,
+        }
+    }
+
+    /// Use the given instruction set instead of the detected one,
+    /// such that tests can check each of them on a CPU supporting several.
+    ///
+    /// # Errors
+    ///
+    /// If the CPU does not support the instruction set.
+    #[doc(hidden)]
+    pub fn with_instruction_set(self, instruction_set: InstructionSet) -> Result<Self> {
+        if !instruction_set.is_supported() {
+            return Err(Error::UnsupportedBackend(format!(
+                "the CPU does not support {instruction_set:?}"
+            )));
+        }
+
+        Ok(Self {
+            instruction_set,
+            ..self
+        })
+    }
+}
+
+impl ConvolveStrategy for Simd {
+    fn convolve(&mut self) -> Result<()> {
+        let input_row_len = self.buffers.input.width() as usize * CHANNELS;
+        // An empty output has no rows, but the chunk size must not be zero.
+        let output_row_len = (self.buffers.output.width() as usize * CHANNELS).max(1);
+        let input = &*self.buffers.input;
+        let kernel = &self.kernel;
+
+        for (y, output) in self
+            .buffers
+            .output
+            .chunks_exact_mut(output_row_len)
+            .enumerate()
+        {
+            output.fill(0.);
+
+            for row in 0..kernel.height() {
+                let start = (y + row as usize) * input_row_len;
+                let input = &input[start..start + input_row_len];
+
+                for col in 0..kernel.width() {
+                    let start = col as usize * CHANNELS;
+                    self.instruction_set.multiply_add(
+                        output,
+                        &input[start..start + output_row_len],
+                        kernel.weight(col, row),
+                    );
+                }
+            }
+
+            for channel in output {
+                *channel = *channel * kernel.normalization() + kernel.bias();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.buffers.finish())
+    }
+}
@@ -0,0 +1,201 @@
+use image::{ColorType, DynamicImage, GenericImageView, Pixel};
+
+use crate::convolution::format;
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::util::{ImageBuffers, ImagePixel};
+
+const CHANNELS: usize = ImagePixel::CHANNEL_COUNT as usize;
+
+/// The weight shared by all of the kernel's weights, if they are all equal.
+fn uniform_weight(kernel: &KernelImpl) -> Option<f32> {
+    let (&first, rest) = kernel.weights().split_first()?;
+
+    rest.iter().all(|&weight| weight == first).then_some(first)
+}
+
+/// The rows of a summed area table of the padded input needed for the current output row.
+///
+/// Entry `(x, y)` holds the sum of the input pixels left of column `x` and above row `y`,
+/// per channel, so the sum under a box is the difference of the entries at its four corners.
+/// Since an output row only needs the table rows at the top and bottom of the kernel,
+/// only the kernel height plus one rows are kept, in a ring.
+///
+/// The sums are accumulated in `f64`, which keeps the differences exact enough for large images.
+struct RollingTable {
+    sums: Vec<f64>,
+    row_len: usize,
+    rows: usize,
+
+    /// How many rows of the table have been computed so far.
+    len: usize,
+}
+
+impl RollingTable {
+    /// The table for an input of the given width, for a kernel of the given height.
+    /// Starts with row zero, which is all zeros.
+    fn new(input_width: u32, kernel_height: u32) -> Self {
+        let row_len = (input_width as usize + 1) * CHANNELS;
+        let rows = kernel_height as usize + 1;
+
+        Self {
+            sums: vec![0.; row_len * rows],
+            row_len,
+            rows,
+            len: 1,
+        }
+    }
+
+    fn start(&self, y: usize) -> usize {
+        y % self.rows * self.row_len
+    }
+
+    /// Compute the next row of the table from the input row above it,
+    /// replacing the oldest row.
+    fn push(&mut self, input_row: &[f32]) {
+        let above = self.start(self.len - 1);
+        let current = self.start(self.len);
+        self.sums.copy_within(above..above + self.row_len, current);
+
+        let row = &mut self.sums[current..current + self.row_len];
+        let mut sums = [0.; CHANNELS];
+        for (pixel, table) in input_row
+            .chunks_exact(CHANNELS)
+            .zip(row[CHANNELS..].chunks_exact_mut(CHANNELS))
+        {
+            for ((sum, &channel), table) in sums.iter_mut().zip(pixel).zip(table) {
+                *sum += channel as f64;
+                *table += *sum;
+            }
+        }
+
+        self.len += 1;
+    }
+
+    /// Row `y` of the table, which must be among the rows kept.
+    fn row(&self, y: usize) -> &[f64] {
+        debug_assert!(y < self.len && y + self.rows >= self.len);
+
+        &self.sums[self.start(y)..][..self.row_len]
+    }
+}
+
+/// A box blur, or any other kernel whose weights are all equal, via a summed area table.
+///
+/// The sum of the input under the kernel is read from four entries of the table,
+/// so the cost per pixel does not depend on the kernel size, which makes this the fastest
+/// backend for large box blurs. See [`KernelImpl::box_blur`] for box blurs of any size.
+///
+/// Repeated box blurs approach a Gaussian blur, three passes already come close,
+/// see [`SummedArea::with_passes`]. The passes are applied one after the other like
+/// [`crate::convolution::Backend::convolve_pipeline`] does,
+/// so the border mode applies to each pass.
+///
+/// Runs on a single thread.
+/// Only supports kernels whose weights are all equal, the normalization and bias may be anything.
+pub struct SummedArea {
+    image: DynamicImage,
+    kernel: KernelImpl,
+    weight: f64,
+    options: Options,
+    passes: u32,
+    color_type: ColorType,
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K)> for SummedArea {
+    type Error = Error;
+
+    fn try_from((input, kernel): (DynamicImage, K)) -> Result<Self> {
+        Self::try_from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> TryFrom<(DynamicImage, K, Options)> for SummedArea {
+    type Error = Error;
+
+    fn try_from((input, kernel, options): (DynamicImage, K, Options)) -> Result<Self> {
+        let kernel = kernel.into();
+        let weight = uniform_weight(&kernel).ok_or_else(|| {
+            Error::UnsupportedKernel(
+                "the summed area table needs a kernel whose weights are all equal, such as a box blur"
+                    .into(),
+            )
+        })?;
+
+        let color_type = input.color();
+        // Like the stages of a pipeline, passes hand floating point images to each other.
+        let image = if color_type.has_alpha() {
+            input.into_rgba32f().into()
+        } else {
+            input.into_rgb32f().into()
+        };
+
+        Ok(Self {
+            image,
+            weight: weight as f64 * kernel.normalization() as f64,
+            kernel,
+            options,
+            passes: 1,
+            color_type,
+        })
+    }
+}
+
+impl SummedArea {
+    /// Blur this many times, at least once.
+    pub fn with_passes(self, passes: u32) -> Self {
+        Self {
+            passes: passes.max(1),
+            ..self
+        }
+    }
+
+    /// Convolve the padded input of the buffers into their output.
+    fn convolve_pass(&self, buffers: &mut ImageBuffers) {
+        // The channels of a row of the kernel, and the rows of the kernel.
+        let kernel_len = self.kernel.width() as usize * CHANNELS;
+        let kernel_height = self.kernel.height() as usize;
+        let bias = self.kernel.bias() as f64;
+        let input_row_len = buffers.input.width() as usize * CHANNELS;
+        let output_row_len = (buffers.output.width() as usize * CHANNELS).max(1);
+
+        let mut table = RollingTable::new(buffers.input.width(), self.kernel.height());
+        let mut input_rows = buffers.input.chunks_exact(input_row_len);
+
+        for (y, output) in buffers.output.chunks_exact_mut(output_row_len).enumerate() {
+            while table.len <= y + kernel_height {
+                table.push(input_rows.next().expect("the input is padded"));
+            }
+
+            let (top, bottom) = (table.row(y), table.row(y + kernel_height));
+            for (left, channel) in output.iter_mut().enumerate() {
+                let right = left + kernel_len;
+                let sum = bottom[right] - bottom[left] - top[right] + top[left];
+
+                *channel = (sum * self.weight + bias) as f32;
+            }
+        }
+    }
+}
+
+impl ConvolveStrategy for SummedArea {
+    fn convolve(&mut self) -> Result<()> {
+        for _ in 0..self.passes {
+            // Cropping shrinks the image with every pass.
+            self.options
+                .check_dimensions(self.image.dimensions(), &self.kernel)?;
+
+            let image = std::mem::take(&mut self.image);
+            let mut buffers = ImageBuffers::new(image, &self.kernel, &self.options);
+            self.convolve_pass(&mut buffers);
+            self.image = buffers.finish();
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(format::restore(self.image.into_rgba32f(), self.color_type))
+    }
+}
@@ -0,0 +1,156 @@
+use image::{DynamicImage, GenericImageView};
+use tracing::debug;
+
+use crate::convolution::{format, strategy::run, Backend};
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+#[cfg(feature = "rayon")]
+use super::multi;
+use super::util::{ImageBuffers, ImagePixel};
+use super::{blocked, separable, simd, single};
+
+/// The number of working buffers per row of a strip, each of the padded width:
+/// the input, the output, the intermediate buffer of the separable backends
+/// and the converted input rows.
+const BUFFERS_PER_ROW: usize = 4;
+
+/// Convolves the image in strips of rows using one of the direct CPU backends,
+/// such that the floating point working buffers stay within a memory budget.
+///
+/// Each strip converts and pads just the input rows it needs, including the rows it
+/// shares with its neighbours due to the kernel height, exactly like the whole image would be.
+/// The output is therefore bit-identical to convolving the whole image with the same backend.
+///
+/// Only the working buffers are bounded. The whole input and output are still held
+/// in their own color type, which for 8 bit RGB is 3 bytes per pixel instead of the 16
+/// of each working buffer, and nothing is decoded or encoded strip by strip.
+/// The peak memory is therefore about the input and the output plus the budget,
+/// e.g. 6 bytes per pixel plus the budget for 8 bit RGB.
+pub struct Tiled {
+    input: DynamicImage,
+    kernel: KernelImpl,
+    options: Options,
+    backend: Backend,
+    strip_rows: u32,
+    output: DynamicImage,
+}
+
+impl Tiled {
+    /// Create a new [`Tiled`] convolution using the given backend,
+    /// keeping the working buffers within about `memory_budget` bytes.
+    /// At least a single row is convolved at a time, however small the budget.
+    ///
+    /// [`Backend::Auto`] chooses between [`Backend::MultiSeparable`] and [`Backend::MultiRayon`],
+    /// or [`Backend::SingleSeparable`] and [`Backend::SingleSimd`] without the `rayon` feature.
+    ///
+    /// # Errors
+    ///
+    /// If the backend cannot convolve in strips, which are [`Backend::Fft`],
+    /// [`Backend::GpuOffscreen`] and [`Backend::Reference`],
+    /// or if the image is too small for the kernel, see [`Options::check_dimensions`].
+    pub fn new<K: Into<KernelImpl>>(
+        input: DynamicImage,
+        kernel: K,
+        options: Options,
+        backend: Backend,
+        memory_budget: usize,
+    ) -> Result<Self> {
+        let kernel = kernel.into();
+        options.check_dimensions(input.dimensions(), &kernel)?;
+
+        let backend = match backend {
+            Backend::Auto if kernel.separate().is_some() => Backend::SEPARABLE,
+            Backend::Auto => Backend::DIRECT,
+            Backend::SingleNestedLoops
+            | Backend::SingleNestedIterators
+            | Backend::SingleSimd
+            | Backend::SingleBlocked
+            | Backend::SingleSeparable => backend,
+            #[cfg(feature = "rayon")]
+            Backend::MultiRayon
+            | Backend::MultiChunked
+            | Backend::MultiBlocked
+            | Backend::MultiSeparable => backend,
+            _ => {
+                return Err(Error::UnsupportedBackend(format!(
+                    "{backend:?} cannot convolve in strips"
+                )))
+            }
+        };
+
+        let (width, height) = options.output_dimensions(input.dimensions(), &kernel);
+        let strip_rows = strip_rows(width, &kernel, memory_budget);
+        debug!(?backend, strip_rows, "Tiled");
+
+        let color_type = format::restored_color_type(input.color());
+        let output = DynamicImage::new(width, height, color_type);
+
+        Ok(Self {
+            input,
+            kernel,
+            options,
+            backend,
+            strip_rows,
+            output,
+        })
+    }
+
+    /// Convolve a single strip using the chosen backend.
+    fn convolve_strip(&self, buffers: ImageBuffers) -> Result<DynamicImage> {
+        let kernel = self.kernel.clone();
+
+        match self.backend {
+            Backend::SingleNestedLoops => run(single::NestedLoops::from_buffers(buffers, kernel)),
+            Backend::SingleNestedIterators => {
+                run(single::NestedIterators::from_buffers(buffers, kernel))
+            }
+            Backend::SingleSimd => run(simd::Simd::from_buffers(buffers, kernel)),
+            Backend::SingleBlocked => run(blocked::Single::from_buffers(buffers, kernel)),
+            #[cfg(feature = "rayon")]
+            Backend::MultiRayon => run(multi::NestedIterators::from_buffers(buffers, kernel)),
+            #[cfg(feature = "rayon")]
+            Backend::MultiChunked => run(multi::Chunked::from_buffers(buffers, kernel)),
+            #[cfg(feature = "rayon")]
+            Backend::MultiBlocked => run(blocked::Multi::from_buffers(buffers, kernel)),
+            Backend::SingleSeparable => run(separable::Single::from_buffers(buffers, kernel)?),
+            #[cfg(feature = "rayon")]
+            Backend::MultiSeparable => run(separable::Multi::from_buffers(buffers, kernel)?),
+            _ => {
+                unreachable!("the backend was checked on creation")
+            }
+        }
+    }
+}
+
+/// How many output rows each strip can have within the memory budget.
+fn strip_rows(width: u32, kernel: &KernelImpl, memory_budget: usize) -> u32 {
+    let row_bytes =
+        std::mem::size_of::<ImagePixel>() * (width + kernel.width()) as usize * BUFFERS_PER_ROW;
+
+    // Each strip also needs the input rows shared with its neighbours.
+    let overlap = kernel.height() as usize - 1;
+    let rows = (memory_budget / row_bytes).saturating_sub(overlap);
+
+    rows.clamp(1, u32::MAX as usize) as u32
+}
+
+impl ConvolveStrategy for Tiled {
+    fn convolve(&mut self) -> Result<()> {
+        let height = self.output.height();
+
+        for start in (0..height).step_by(self.strip_rows as usize) {
+            let rows = start..(start + self.strip_rows).min(height);
+            let buffers = ImageBuffers::strip(&self.input, &self.kernel, &self.options, rows);
+
+            let strip = self.convolve_strip(buffers)?;
+            format::copy_rows(&mut self.output, &strip, start);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(self.output)
+    }
+}
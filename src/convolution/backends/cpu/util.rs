@@ -1,78 +1,285 @@
-use image::{DynamicImage, GenericImageView, Pixel, SubImage};
+use std::{collections::BTreeMap, ops::Range};
 
+use image::{ColorType, DynamicImage, GenericImageView, Pixel, SubImage};
+
+use crate::convolution::format;
 use crate::kernel::KernelImpl;
+use crate::prelude::*;
 
 /// The type of image pixel we will be working with on the CPU.
-pub type ImagePixel = image::Rgb<f32>;
+pub type ImagePixel = image::Rgba<f32>;
 /// The type of image we will be working with.
 pub type Image = image::ImageBuffer<ImagePixel, Vec<f32>>;
 
+/// The input and output buffers of a convolution.
+///
+/// The input is padded according to the [`BorderMode`], such that
+/// the kernel centered on output pixel `(x, y)` covers the input pixels starting
+/// at `(x, y)` and spanning the kernel width and height.
+/// Backends therefore never have to special case the edges of the image.
+///
+/// All channels are convolved, and [`ImageBuffers::finish`] takes care of
+/// the alpha mode, premultiplication and converting back to the color type of the input.
 #[derive(Debug)]
 pub(crate) struct ImageBuffers {
     pub input: Image,
     pub output: Image,
+    radius: (u32, u32),
+    color_type: ColorType,
+    alpha: AlphaMode,
+    premultiply: bool,
+    color_space: ColorSpace,
 }
 
 impl ImageBuffers {
-    pub(crate) fn dimensions(&self) -> (usize, usize) {
-        (self.input.width() as usize, self.input.height() as usize)
+    pub(crate) fn new(input: DynamicImage, kernel: &KernelImpl, options: &Options) -> Self {
+        let (width, height) = options.output_dimensions(input.dimensions(), kernel);
+        let output = Image::new(width, height);
+        let color_type = input.color();
+
+        let mut input = input.into_rgba32f();
+        input
+            .pixels_mut()
+            .for_each(|pixel| to_working_space(pixel, options));
+        let input = pad(input, kernel, options);
+
+        Self::from_parts(input, output, kernel, options, color_type)
+    }
+
+    /// Buffers for only the given rows of the output, such that an image can be
+    /// convolved in strips, see [`super::tiled`].
+    ///
+    /// The input holds just the (padded) rows needed for those output rows,
+    /// which are the same as the corresponding rows of the input for the whole image.
+    pub(crate) fn strip(
+        input: &DynamicImage,
+        kernel: &KernelImpl,
+        options: &Options,
+        rows: Range<u32>,
+    ) -> Self {
+        let (width, _) = options.output_dimensions(input.dimensions(), kernel);
+        let output = Image::new(width, rows.len() as u32);
+        let color_type = input.color();
+
+        let input = pad_strip(input, kernel, options, rows);
+
+        Self::from_parts(input, output, kernel, options, color_type)
+    }
+
+    fn from_parts(
+        input: Image,
+        output: Image,
+        kernel: &KernelImpl,
+        options: &Options,
+        color_type: ColorType,
+    ) -> Self {
+        Self {
+            input,
+            output,
+            radius: kernel.radius(),
+            color_type,
+            alpha: options.alpha,
+            premultiply: options.premultiply,
+            color_space: options.color_space,
+        }
+    }
+
+    /// Produce the final image from the output buffer.
+    pub(crate) fn finish(mut self) -> DynamicImage {
+        // Premultiplied colors were convolved along with alpha,
+        // so the convolved alpha is what they must be divided by.
+        if self.premultiply {
+            self.output.pixels_mut().for_each(unpremultiply);
+        }
+
+        if self.color_space == ColorSpace::Linear {
+            self.output
+                .pixels_mut()
+                .for_each(|pixel| pixel.apply_without_alpha(format::linear_to_srgb));
+        }
+
+        if self.alpha == AlphaMode::PassThrough {
+            let (radius_x, radius_y) = self.radius;
+
+            // The input pixel the kernel was centered on, see [`ImageBuffers`].
+            for (x, y, pixel) in self.output.enumerate_pixels_mut() {
+                pixel[3] = self.input.get_pixel(x + radius_x, y + radius_y)[3];
+            }
+        }
+
+        format::restore(self.output, self.color_type)
     }
 }
 
-impl ImageBuffers {
-    pub(crate) fn new(input: DynamicImage) -> Self {
-        let output = Image::new(input.width(), input.height());
-        let input = input.to_rgb32f();
+/// Convert an input pixel to the color space the convolution is performed in,
+/// premultiplying it if needed.
+#[inline(always)]
+fn to_working_space(pixel: &mut ImagePixel, options: &Options) {
+    if options.color_space == ColorSpace::Linear {
+        pixel.apply_without_alpha(format::srgb_to_linear);
+    }
+
+    if options.premultiply {
+        premultiply(pixel);
+    }
+}
+
+/// Multiply the color channels by alpha.
+#[inline(always)]
+pub(crate) fn premultiply(pixel: &mut ImagePixel) {
+    let alpha = pixel[3];
+    pixel.apply_without_alpha(|channel| channel * alpha);
+}
+
+/// Alpha values below this are considered fully transparent when unpremultiplying.
+/// This is below what a 16 bit alpha channel can represent, and
+/// avoids dividing by rounding noise.
+pub(crate) const TRANSPARENT_ALPHA: f32 = 1. / 65535.;
+
+/// Divide the color channels by alpha.
+/// Fully transparent pixels have no meaningful color, and get black color channels.
+#[inline(always)]
+pub(crate) fn unpremultiply(pixel: &mut ImagePixel) {
+    let alpha = pixel[3];
+
+    if alpha >= TRANSPARENT_ALPHA {
+        pixel.apply_without_alpha(|channel| channel / alpha);
+    } else {
+        pixel.apply_without_alpha(|_| 0.);
+    }
+}
+
+/// Pads the image by the kernel radius on each side,
+/// filling the padding as described by the border mode.
+/// Cropping requires no padding.
+fn pad(image: Image, kernel: &KernelImpl, options: &Options) -> Image {
+    if options.border == BorderMode::Crop {
+        return image;
+    }
 
-        Self { input, output }
+    let (radius_x, radius_y) = kernel.radius();
+    let (width, height) = image.dimensions();
+    let mut border_color = ImagePixel::from(options.border_color);
+    to_working_space(&mut border_color, options);
+
+    Image::from_fn(width + 2 * radius_x, height + 2 * radius_y, |x, y| {
+        let x = options
+            .border
+            .source_index(x as i64 - radius_x as i64, width);
+        let y = options
+            .border
+            .source_index(y as i64 - radius_y as i64, height);
+
+        match (x, y) {
+            (Some(x), Some(y)) => *image.get_pixel(x, y),
+            _ => border_color,
+        }
+    })
+}
+
+/// Like [`pad`], but converts the image to the working space as well, and
+/// only produces the padded rows needed for the given output rows.
+/// Only the input rows needed for those are converted.
+fn pad_strip(
+    image: &DynamicImage,
+    kernel: &KernelImpl,
+    options: &Options,
+    rows: Range<u32>,
+) -> Image {
+    let (radius_x, radius_y) = match options.border {
+        BorderMode::Crop => (0, 0),
+        _ => kernel.radius(),
+    };
+    let (width, height) = image.dimensions();
+    let mut border_color = ImagePixel::from(options.border_color);
+    to_working_space(&mut border_color, options);
+
+    // The input row or column each padded row or column is read from, if any.
+    let source_rows: Vec<Option<u32>> = (rows.start..rows.end + kernel.height() - 1)
+        .map(|y| {
+            options
+                .border
+                .source_index(y as i64 - radius_y as i64, height)
+        })
+        .collect();
+    let source_columns: Vec<Option<u32>> = (0..width + 2 * radius_x)
+        .map(|x| {
+            options
+                .border
+                .source_index(x as i64 - radius_x as i64, width)
+        })
+        .collect();
+
+    let mut converted = BTreeMap::new();
+    for &y in source_rows.iter().flatten() {
+        converted.entry(y).or_insert_with(|| {
+            let mut row = image.crop_imm(0, y, width, 1).into_rgba32f();
+            row.pixels_mut()
+                .for_each(|pixel| to_working_space(pixel, options));
+            row
+        });
     }
+
+    let mut padded = Image::new(source_columns.len() as u32, source_rows.len() as u32);
+    for (row, source_row) in padded.rows_mut().zip(&source_rows) {
+        let source_row = source_row.map(|y| &converted[&y]);
+
+        for (pixel, source_column) in row.zip(&source_columns) {
+            *pixel = match (source_row, source_column) {
+                (Some(source_row), Some(x)) => *source_row.get_pixel(*x, 0),
+                _ => border_color,
+            };
+        }
+    }
+
+    padded
 }
 
 /// Apply a convolution.
-/// The weights are fetched from the given [`Kernel`].
-/// Normalization is applied.
+/// The weights are fetched from the given [`KernelImpl`].
+/// Normalization and bias are applied.
 ///
-/// The provided 3x3 view into an image must be able to be
-/// iterated over using 0..3 indexing in both coordinates,
-/// and should result in reading the 3x3 neighbourhood
+/// The provided view into an image must be able to be
+/// iterated over using `0..kernel.width()` and `0..kernel.height()` indexing,
+/// and should result in reading the neighbourhood
 /// centered around the output pixel we're interested in.
 ///
 /// Safety: The view must be safe to access with the indices described above.
 #[inline(always)]
 pub fn do_convolve(
-    kernel: KernelImpl,
+    kernel: &KernelImpl,
     pixel: &mut ImagePixel,
-    view_3x3: &dyn GenericImageView<Pixel = ImagePixel>,
+    view: &dyn GenericImageView<Pixel = ImagePixel>,
 ) {
-    for row in 0..3 {
-        for col in 0..3 {
+    for row in 0..kernel.height() {
+        for col in 0..kernel.width() {
+            let weight = kernel.weight(col, row);
             unsafe {
                 pixel.apply2(
-                    &view_3x3.unsafe_get_pixel(col, row),
-                    |output_channel, input_channel| {
-                        output_channel
-                            + input_channel * kernel.weights[col as usize + row as usize * 3]
-                    },
+                    &view.unsafe_get_pixel(col, row),
+                    |output_channel, input_channel| output_channel + input_channel * weight,
                 );
             }
         }
     }
-    pixel.apply(|channel| channel * kernel.normalization)
+    pixel.apply(|channel| channel * kernel.normalization() + kernel.bias())
 }
 
-pub type Kernel3x3<'i> = SubImage<&'i Image>;
+pub type KernelView<'i> = SubImage<&'i Image>;
 
-/// Creates a view into an image of a 3x3 pixel area.
+/// Creates a view into a padded input image of the pixel area covered by the kernel
+/// when centered on the given output row and column.
+/// See [`ImageBuffers`].
 ///
 /// # Panics
 ///
 /// If there isn't space to create the pixel area.
 #[inline(always)]
-pub fn view3x3(image: &Image, row: u32, column: u32) -> Kernel3x3 {
-    debug_assert!(row != 0);
-    debug_assert!(row < image.height() - 1);
-    debug_assert!(column != 0);
-    debug_assert!(column < image.width() - 1);
-
-    image.view(column - 1, row - 1, 3, 3)
+pub fn view_kernel<'i>(
+    image: &'i Image,
+    kernel: &KernelImpl,
+    row: u32,
+    column: u32,
+) -> KernelView<'i> {
+    image.view(column, row, kernel.width(), kernel.height())
 }
@@ -1,25 +1,50 @@
-use std::sync::Arc;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+};
 
 use image::DynamicImage;
-use tokio::runtime::Runtime;
 use wgpu::{Adapter, Device, Queue, RenderPipeline};
 
 use crate::prelude::*;
 
 use super::{texture, FORMAT};
 
-/// GPU data context.
-/// Useful for benchmarks, since it allows setting up a GPU context (and related resources) once,
-/// when benchmarks must run hundreds or thousands of times.
+/// The GPU device along with the resources which do not depend on the image,
+/// such that it can be shared by the contexts of many images.
 #[derive(Debug)]
-pub struct GpuData {
+pub struct GpuDevice {
     /// Device.
     pub device: wgpu::Device,
     /// Queue.
     pub queue: wgpu::Queue,
 
-    /// The bind group holding the diffuse
-    /// texture and its sampler.
+    /// The layout of the bind group holding the diffuse texture.
+    pub texture_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// The layout of the bind group holding the kernel parameters and weights.
+    pub kernel_bind_group_layout: wgpu::BindGroupLayout,
+
+    /// The render pipeline running the convolution.
+    /// Kernels are passed via bind groups, so this is shared by all kernels.
+    pub render_pipeline: RenderPipeline,
+
+    /// Held from pushing to popping the error scopes, see [`GpuDevice::scoped`].
+    scope_lock: Mutex<()>,
+}
+
+/// The device shared by all contexts in this process, see [`GpuDevice::shared`].
+static SHARED_DEVICE: Mutex<Option<Arc<GpuDevice>>> = Mutex::new(None);
+
+/// GPU data context.
+/// Useful for benchmarks, since it allows setting up a GPU context (and related resources) once,
+/// when benchmarks must run hundreds or thousands of times.
+#[derive(Debug)]
+pub struct GpuData {
+    /// The device this context was created on.
+    pub gpu: Arc<GpuDevice>,
+
+    /// The bind group holding the diffuse texture.
     pub diffuse_bind_group: wgpu::BindGroup,
 
     /// The texture we'll render to instead of e.g.
@@ -30,11 +55,8 @@ pub struct GpuData {
     /// such that we can map it.
     pub output_gpu_buffer: texture::OutputBuffer,
 
-    /// The shader containing the vertex- and fragment programs.
-    pub shader: wgpu::ShaderModule,
-
-    /// The layout of the render pipeline.
-    pub render_pipeline_layout: wgpu::PipelineLayout,
+    /// The color type of the input image, which the output is converted back to.
+    pub color_type: image::ColorType,
 }
 
 /// A clonable context.
@@ -57,103 +79,134 @@ async fn prepare_wgpu() -> Result<(Adapter, Device, Queue)> {
             ..Default::default()
         })
         .await
-        .ok_or_else(|| Error::Gpu("No adapter".into()))?;
+        .ok_or(Error::AdapterUnavailable)?;
 
     let (device, queue) = adapter
         .request_device(&wgpu::DeviceDescriptor::default(), None)
         .await
-        .map_err(|e| Error::Gpu(format!("{e:?}")))?;
+        .map_err(|e| Error::Gpu(format!("failed to request a device: {e}")))?;
 
     Ok((adapter, device, queue))
 }
 
-impl GpuCtx {
-    /// Create a GPU context based on some image.
-    /// The size of this image determines the size of GPU related
-    /// textures and buffers.
-    pub fn new(diffuse: DynamicImage) -> Result<Self> {
-        Runtime::new()
-            .unwrap()
-            .block_on(async { Self::async_new(diffuse).await })
+/// Runs a future to completion on the current thread.
+pub(crate) fn block_on<F: Future>(future: F) -> Result<F::Output> {
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+    Ok(runtime.block_on(future))
+}
+
+/// Capture the validation and out of memory errors of the following GPU operations,
+/// until [`pop_error_scopes`]. Uncaptured errors would otherwise panic.
+fn push_error_scopes(device: &Device) {
+    device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+}
+
+/// Return the first error captured since [`push_error_scopes`], if any.
+async fn pop_error_scopes(device: &Device) -> Result<()> {
+    let validation = device.pop_error_scope().await;
+    let out_of_memory = device.pop_error_scope().await;
+
+    match validation.or(out_of_memory) {
+        Some(e) => Err(Error::Gpu(e.to_string())),
+        None => Ok(()),
     }
+}
+
+impl GpuDevice {
+    /// Get the device shared by all contexts in this process, creating it on first use.
+    ///
+    /// Setting up a device is slow compared to convolving a single image,
+    /// so reusing it pays off when convolving many images in one process.
+    pub fn shared() -> Result<Arc<Self>> {
+        let mut shared = SHARED_DEVICE.lock().unwrap_or_else(PoisonError::into_inner);
 
-    /// Create a render pipeline based on the given kernel.
-    /// The kernel specialized which fragment shader program runs, possibly
-    /// allowing for optimizations.
-    pub fn render_pipeline(&self, kernel: Kernel) -> RenderPipeline {
-        let fs_entry = frag_entry_point(kernel);
-
-        self.inner
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some(&format!("Render Pipeline: {fs_entry}")),
-                layout: Some(&self.inner.render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &self.inner.shader,
-                    entry_point: "vs_fullscreen",
-                    buffers: &[],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &self.inner.shader,
-                    entry_point: fs_entry,
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: FORMAT,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            })
+        if let Some(device) = shared.as_ref() {
+            return Ok(device.clone());
+        }
+
+        let device = Arc::new(Self::new()?);
+        *shared = Some(device.clone());
+
+        Ok(device)
     }
 
-    /// Create a new GPU context and all needed resources.
-    async fn async_new(diffuse: DynamicImage) -> Result<Self> {
-        let (_adapter, device, queue) = prepare_wgpu().await?;
+    /// Create a new device, not shared with other contexts.
+    ///
+    /// # Errors
+    ///
+    /// If there is no GPU adapter, or the device or pipeline cannot be created.
+    pub fn new() -> Result<Self> {
+        block_on(Self::async_new())?
+    }
+
+    /// Run GPU operations, returning the validation or out of memory errors they cause
+    /// as [`Error::Gpu`] instead of panicking.
+    ///
+    /// Error scopes belong to the device rather than the thread, so threads sharing the device
+    /// take turns, lest they pop each other's scopes. The operations must therefore not
+    /// call this again.
+    pub(crate) fn scoped<T>(&self, operations: impl FnOnce() -> T) -> Result<T> {
+        let _lock = self
+            .scope_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
 
-        let (diffuse_texture, render_texture, output_gpu_buffer) =
-            texture::prepare(&device, &queue, diffuse)?;
+        push_error_scopes(&self.device);
+        let value = operations();
+        block_on(pop_error_scopes(&self.device))??;
+
+        Ok(value)
+    }
+
+    /// Create a new device and the resources which do not depend on the image.
+    async fn async_new() -> Result<Self> {
+        let (_adapter, device, queue) = prepare_wgpu().await?;
+        push_error_scopes(&device);
 
         let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                }],
+                label: Some("texture_bind_group_layout"),
+            });
+
+        let kernel_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
                     wgpu::BindGroupLayoutEntry {
                         binding: 0,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
                         },
                         count: None,
                     },
                     wgpu::BindGroupLayoutEntry {
                         binding: 1,
                         visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
                         count: None,
                     },
                 ],
-                label: Some("texture_bind_group_layout"),
+                label: Some("kernel_bind_group_layout"),
             });
 
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
-
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
@@ -162,31 +215,87 @@ impl GpuCtx {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout],
+                bind_group_layouts: &[&texture_bind_group_layout, &kernel_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_convolve",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: FORMAT,
+                    // Float formats are not blendable, which is fine since we replace anyway.
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        pop_error_scopes(&device).await?;
+
+        Ok(Self {
+            device,
+            queue,
+            texture_bind_group_layout,
+            kernel_bind_group_layout,
+            render_pipeline,
+            scope_lock: Mutex::new(()),
+        })
+    }
+}
+
+impl GpuCtx {
+    /// Create a GPU context based on some image, on the device shared by this process.
+    /// The size of this image determines the size of GPU related
+    /// textures and buffers.
+    pub fn new(diffuse: DynamicImage) -> Result<Self> {
+        Self::with_device(GpuDevice::shared()?, diffuse)
+    }
+
+    /// Create a GPU context based on some image, on the given device.
+    ///
+    /// # Errors
+    ///
+    /// If the textures cannot be created, e.g. because the image is larger than the device supports.
+    pub fn with_device(gpu: Arc<GpuDevice>, diffuse: DynamicImage) -> Result<Self> {
+        let color_type = diffuse.color();
+
+        let (diffuse_bind_group, render_texture, output_gpu_buffer) = gpu.scoped(|| {
+            let (diffuse_texture, render_texture, output_gpu_buffer) =
+                texture::prepare(&gpu.device, &gpu.queue, diffuse)?;
+
+            let diffuse_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &gpu.texture_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                }],
+                label: Some("diffuse_bind_group"),
+            });
+
+            Result::Ok((diffuse_bind_group, render_texture, output_gpu_buffer))
+        })??;
+
         Ok(Self {
             inner: Arc::new(GpuData {
-                device,
-                queue,
+                gpu,
                 diffuse_bind_group,
                 render_texture,
                 output_gpu_buffer,
-                shader,
-                render_pipeline_layout,
+                color_type,
             }),
         })
     }
 }
-
-fn frag_entry_point(kernel: Kernel) -> &'static str {
-    match kernel {
-        Kernel::Identity => "fs_identity",
-        Kernel::EdgeDetection1 => "fs_edge_detection1",
-        Kernel::EdgeDetection2 => "fs_edge_detection2",
-        Kernel::Sharpen => "fs_sharpen",
-        Kernel::BoxBlur => "fs_box_blur",
-        Kernel::GaussianBlur => "fs_gaussian_blur",
-    }
-}
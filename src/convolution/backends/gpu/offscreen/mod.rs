@@ -1,43 +1,182 @@
-use self::context::GpuCtx;
+use self::context::{GpuCtx, GpuDevice};
+use crate::convolution::format;
+use crate::kernel::KernelImpl;
 use crate::prelude::*;
-use image::{Pixel, Rgba, RgbaImage};
+use image::Rgba32FImage;
 use std::iter;
 use tokio::sync::oneshot;
-use wgpu::RenderPipeline;
+use wgpu::util::DeviceExt;
 
 /// Context necessary for running GPU backends.
 pub mod context;
 
 pub(crate) mod texture;
 
-pub(crate) const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+/// A float format keeps the precision of 16 bit and float images,
+/// and is not subject to sRGB conversions.
+pub(crate) const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
 
 /// GPU offscreen convolution.
 #[derive(Debug)]
 pub struct Offscreen {
     ctx: context::GpuCtx,
-    render_pipeline: RenderPipeline,
-    output_cpu_buffer: RgbaImage,
+    /// One per kernel, in the order they are applied.
+    kernel_bind_groups: Vec<wgpu::BindGroup>,
+    /// Only needed when there is more than one kernel, see [`Scratch`].
+    scratch: Option<Scratch>,
+    output_cpu_buffer: Rgba32FImage,
+}
+
+/// The texture the stages of a pipeline alternate with the render texture of the context.
+/// The last stage renders to the render texture, the one before it to the scratch texture,
+/// and so on, such that no stage reads the texture it renders to.
+#[derive(Debug)]
+struct Scratch {
+    texture: texture::RenderTexture,
+
+    /// Binds the scratch texture as the input of a stage.
+    bind_group: wgpu::BindGroup,
+
+    /// Binds the render texture of the context as the input of a stage.
+    render_bind_group: wgpu::BindGroup,
+}
+
+impl Scratch {
+    fn new(context: &GpuCtx) -> Result<Self> {
+        let gpu = &context.inner.gpu;
+        let extent = context.inner.render_texture.extent;
+        let texture = texture::RenderTexture::new(&gpu.device, (extent.width, extent.height))?;
+
+        let bind = |view, label| {
+            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &gpu.texture_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view),
+                }],
+                label: Some(label),
+            })
+        };
+
+        Ok(Self {
+            bind_group: bind(&texture.view, "scratch_bind_group"),
+            render_bind_group: bind(&context.inner.render_texture.view, "render_bind_group"),
+            texture,
+        })
+    }
+}
+
+/// The uniform kernel parameters passed to the shader.
+/// Must match `KernelParams` in the shader, including its padding.
+struct KernelParams<'k> {
+    kernel: &'k KernelImpl,
+    options: &'k Options,
+}
+
+impl KernelParams<'_> {
+    fn to_bytes(&self) -> Vec<u8> {
+        let [red, green, blue, alpha] = self.options.border_color;
+
+        let border_mode: u32 = match self.options.border {
+            BorderMode::Clamp => 0,
+            BorderMode::Mirror => 1,
+            BorderMode::Wrap => 2,
+            BorderMode::Constant => 3,
+            BorderMode::Crop => 4,
+        };
+
+        let alpha_mode: u32 = match self.options.alpha {
+            AlphaMode::PassThrough => 0,
+            AlphaMode::Convolve => 1,
+        };
+
+        [
+            // size: vec2<i32>
+            self.kernel.width(),
+            self.kernel.height(),
+            border_mode,
+            self.kernel.normalization().to_bits(),
+            // border_color: vec4<f32>
+            red.to_bits(),
+            green.to_bits(),
+            blue.to_bits(),
+            alpha.to_bits(),
+            self.kernel.bias().to_bits(),
+            alpha_mode,
+            u32::from(self.options.premultiply),
+            u32::from(self.options.color_space == ColorSpace::Linear),
+        ]
+        .into_iter()
+        .flat_map(u32::to_ne_bytes)
+        .collect()
+    }
+}
+
+/// The bind group holding the parameters and weights of a kernel.
+fn kernel_bind_group(gpu: &GpuDevice, kernel: &KernelImpl, options: &Options) -> wgpu::BindGroup {
+    let params = gpu
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Kernel Params Buffer"),
+            contents: &KernelParams { kernel, options }.to_bytes(),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+    let weights = gpu
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Kernel Weights Buffer"),
+            contents: &kernel
+                .weights()
+                .iter()
+                .flat_map(|weight| weight.to_ne_bytes())
+                .collect::<Vec<_>>(),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &gpu.kernel_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: weights.as_entire_binding(),
+            },
+        ],
+        label: Some("kernel_bind_group"),
+    })
 }
 
 impl ConvolveStrategy for Offscreen {
     fn convolve(&mut self) -> Result<()> {
+        let gpu = &self.ctx.inner.gpu;
+
         // Execute the pipeline on the GPU.
-        let idx = self.render();
+        let idx = gpu.scoped(|| self.render())?;
 
         // The rest is mapping the GPU buffer to CPU side and then
         // creating an image from it.
 
         let buffer_slice = self.ctx.inner.output_gpu_buffer.buffer.slice(..);
-        let (tx, rx) = oneshot::channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |res| tx.send(res).unwrap());
+        let (tx, mut rx) = oneshot::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |res| {
+            // Nobody is waiting for the result if the receiver is gone.
+            let _ = tx.send(res);
+        });
 
-        self.ctx
-            .inner
-            .device
+        // Waiting for the submission also runs the mapping callback,
+        // so the result is there unless something went wrong.
+        gpu.device
             .poll(wgpu::MaintainBase::WaitForSubmissionIndex(idx));
 
-        rx.blocking_recv().unwrap().unwrap();
+        rx.try_recv()
+            .map_err(|e| {
+                Error::BufferMapFailed(format!("no result after the GPU finished rendering ({e})"))
+            })?
+            .map_err(|e| Error::BufferMapFailed(e.to_string()))?;
 
         let padded_buffer = buffer_slice.get_mapped_range();
 
@@ -52,8 +191,12 @@ impl ConvolveStrategy for Offscreen {
 
         for ((_, buf_out), buf_in) in out_rows.zip(in_rows) {
             buf_out.for_each(|(col, _, pixel)| {
-                let pos = col as usize * 4;
-                *pixel = *Rgba::from_slice(&buf_in[pos..(pos + 4)]);
+                let pos = col as usize * 16;
+                let bytes = buf_in[pos..(pos + 16)].chunks_exact(4);
+
+                for (channel, bytes) in pixel.0.iter_mut().zip(bytes) {
+                    *channel = f32::from_ne_bytes(bytes.try_into().expect("4 bytes per channel"));
+                }
             });
         }
 
@@ -64,39 +207,108 @@ impl ConvolveStrategy for Offscreen {
     }
 
     fn finish(self) -> Result<image::DynamicImage> {
-        Ok(self.output_cpu_buffer.into())
+        Ok(format::restore(
+            self.output_cpu_buffer,
+            self.ctx.inner.color_type,
+        ))
     }
 }
 
 impl Offscreen {
-    /// Create a new [`Offscreen`] instance with the given [`GpuCtx`] and [`Kernel`].
-    pub fn new(context: GpuCtx, kernel: Kernel) -> Result<Self> {
+    /// Create a new [`Offscreen`] instance with the given [`GpuCtx`], kernel and [`Options`].
+    ///
+    /// # Errors
+    ///
+    /// If the image is too small for the kernel, see [`Options::check_dimensions`],
+    /// or if the kernel buffers cannot be created on the device.
+    pub fn new<K: Into<KernelImpl>>(context: GpuCtx, kernel: K, options: Options) -> Result<Self> {
+        Self::pipeline(context, [kernel], options)
+    }
+
+    /// Create a new [`Offscreen`] instance convolving with each of the kernels in turn,
+    /// like [`crate::convolution::Backend::convolve_pipeline`], but without leaving the GPU
+    /// in between the stages.
+    ///
+    /// # Errors
+    ///
+    /// If there are no kernels, if the image is too small for the kernels,
+    /// see [`Options::check_dimensions`], or if the buffers cannot be created on the device.
+    pub fn pipeline<K: Into<KernelImpl>>(
+        context: GpuCtx,
+        kernels: impl IntoIterator<Item = K>,
+        options: Options,
+    ) -> Result<Self> {
+        let gpu = &context.inner.gpu;
         let dims = &context.inner.output_gpu_buffer.dimensions;
-        let output_cpu_buffer = RgbaImage::new(dims.width as u32, dims.height as u32);
+        let mut dimensions = (dims.width as u32, dims.height as u32);
+
+        let kernel_bind_groups = kernels
+            .into_iter()
+            .map(|kernel| {
+                let kernel = kernel.into();
+                // Cropping shrinks the image with every stage.
+                options.check_dimensions(dimensions, &kernel)?;
+                dimensions = options.output_dimensions(dimensions, &kernel);
+
+                gpu.scoped(|| kernel_bind_group(gpu, &kernel, &options))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let scratch = match kernel_bind_groups.len() {
+            0 => {
+                return Err(Error::InvalidInput(
+                    "a pipeline needs at least one kernel".into(),
+                ))
+            }
+            1 => None,
+            _ => Some(gpu.scoped(|| Scratch::new(&context))??),
+        };
 
-        let render_pipeline = context.render_pipeline(kernel);
+        // When cropping, the output is smaller than the render texture.
+        // The remaining texels are rendered but not read back.
+        let (width, height) = dimensions;
+        let output_cpu_buffer = Rgba32FImage::new(width, height);
 
         Ok(Self {
             ctx: context,
+            kernel_bind_groups,
+            scratch,
             output_cpu_buffer,
-            render_pipeline,
         })
     }
 
-    fn render(&mut self) -> wgpu::SubmissionIndex {
+    fn render(&self) -> wgpu::SubmissionIndex {
         let mut encoder =
             self.ctx
                 .inner
+                .gpu
                 .device
                 .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                     label: Some("Render Encoder"),
                 });
 
-        {
+        let inner = &self.ctx.inner;
+        let scratch = || {
+            self.scratch
+                .as_ref()
+                .expect("pipelines of several stages have a scratch texture")
+        };
+        let last = self.kernel_bind_groups.len() - 1;
+
+        for (stage, kernel_bind_group) in self.kernel_bind_groups.iter().enumerate() {
+            // Counting back from the last stage, which renders to the render texture.
+            let to_scratch = (last - stage) % 2 == 1;
+            let (source, target) = match (stage, to_scratch) {
+                (0, false) => (&inner.diffuse_bind_group, &inner.render_texture.view),
+                (0, true) => (&inner.diffuse_bind_group, &scratch().texture.view),
+                (_, false) => (&scratch().bind_group, &inner.render_texture.view),
+                (_, true) => (&scratch().render_bind_group, &scratch().texture.view),
+            };
+
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &self.ctx.inner.render_texture.view,
+                    view: target,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
@@ -106,8 +318,9 @@ impl Offscreen {
                 depth_stencil_attachment: None,
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.ctx.inner.diffuse_bind_group, &[]);
+            render_pass.set_pipeline(&inner.gpu.render_pipeline);
+            render_pass.set_bind_group(0, source, &[]);
+            render_pass.set_bind_group(1, kernel_bind_group, &[]);
             render_pass.draw(0..3, 0..1);
         }
 
@@ -133,6 +346,10 @@ impl Offscreen {
             self.ctx.inner.render_texture.extent,
         );
 
-        self.ctx.inner.queue.submit(iter::once(encoder.finish()))
+        self.ctx
+            .inner
+            .gpu
+            .queue
+            .submit(iter::once(encoder.finish()))
     }
 }
@@ -1,4 +1,4 @@
-//! Fullscreen vertex shader like Bevy does, see: https://github.com/bevyengine/bevy/blob/main/crates/bevy_core_pipeline/src/fullscreen_vertex_shader/fullscreen.wgsl 
+//! Fullscreen vertex shader like Bevy does, see: https://github.com/bevyengine/bevy/blob/main/crates/bevy_core_pipeline/src/fullscreen_vertex_shader/fullscreen.wgsl
 
 struct FullscreenVertexOutput {
     @builtin(position)
@@ -17,138 +17,137 @@ fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> FullscreenVertexOu
 
 @group(0) @binding(0)
 var t: texture_2d<f32>;
-@group(0)@binding(1)
-var s: sampler;
 
+// Must match `BorderMode` on the Rust side.
+const BORDER_CLAMP: u32 = 0u;
+const BORDER_MIRROR: u32 = 1u;
+const BORDER_WRAP: u32 = 2u;
+const BORDER_CONSTANT: u32 = 3u;
+const BORDER_CROP: u32 = 4u;
+
+// Must match `AlphaMode` on the Rust side.
+const ALPHA_PASS_THROUGH: u32 = 0u;
+const ALPHA_CONVOLVE: u32 = 1u;
+
+// Must match `KernelParams` on the Rust side.
+struct KernelParams {
+	size: vec2<i32>,
+	border_mode: u32,
+	normalization: f32,
+	border_color: vec4<f32>,
+	bias: f32,
+	alpha_mode: u32,
+	premultiply: u32,
+	linear: u32,
+};
 
-@fragment
-fn fs_identity(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
+// Alpha values below this are considered fully transparent when unpremultiplying.
+// Must match `TRANSPARENT_ALPHA` on the Rust side.
+const TRANSPARENT_ALPHA: f32 = 1.52590219e-5;
 
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
+@group(1) @binding(0)
+var<uniform> params: KernelParams;
 
-	// normalization
-	// rgb /= 1.;
+// Weights from top-left to bottom-right, row by row.
+@group(1) @binding(1)
+var<storage, read> weights: array<f32>;
 
-	return vec4(rgb, 1.);
+// The non-negative remainder of `a / b`, for positive `b`.
+// WGSL defines `%` for negative operands, but the GL backend translates it to GLSL as is,
+// where the result is undefined. E.g. llvmpipe computes it as if `a` were unsigned.
+fn rem_euclid(a: i32, b: i32) -> i32 {
+	let r = abs(a) % b;
+	return select(r, (b - r) % b, a < 0);
 }
 
-@fragment
-fn fs_edge_detection1(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
-
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 4. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
-
-	// normalization
-	// rgb /= 1.;
-
-	return vec4(rgb, 1.);
+// Maps a possibly out of bounds index along an axis of length `len`
+// to the index of the pixel which should be read instead.
+// Returns -1 if the border color should be used.
+fn source_index(index: i32, len: i32) -> i32 {
+	if params.border_mode == BORDER_MIRROR {
+		let period = 2 * len;
+		let i = rem_euclid(index, period);
+		return select(period - 1 - i, i, i < len);
+	} else if params.border_mode == BORDER_WRAP {
+		return rem_euclid(index, len);
+	} else if params.border_mode == BORDER_CONSTANT {
+		return select(-1, index, index >= 0 && index < len);
+	}
+
+	// BORDER_CLAMP, and BORDER_CROP which never reads out of bounds.
+	return clamp(index, 0, len - 1);
 }
 
-@fragment
-fn fs_edge_detection2(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
-
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
-
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 8. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
-
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
-
-	// normalization
-	// rgb /= 1.;
-
-	return vec4(rgb, 1.);
+// Must match `format::srgb_to_linear` on the Rust side.
+fn srgb_to_linear(c: vec3<f32>) -> vec3<f32> {
+	return select(pow((c + 0.055) / 1.055, vec3(2.4)), c / 12.92, c <= vec3(0.04045));
 }
 
-@fragment
-fn fs_sharpen(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
-
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 5. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
-
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	rgb += -1. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	// rgb += 0. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
-
-	// normalization
-	// rgb /= 1.;
-
-	return vec4(rgb, 1.);
+// Must match `format::linear_to_srgb` on the Rust side.
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+	return select(1.055 * pow(c, vec3(1. / 2.4)) - 0.055, c * 12.92, c <= vec3(0.0031308));
 }
 
-@fragment
-fn fs_box_blur(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
+fn load(position: vec2<i32>, dimensions: vec2<i32>) -> vec4<f32> {
+	let x = source_index(position.x, dimensions.x);
+	let y = source_index(position.y, dimensions.y);
 
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
+	var rgba = params.border_color;
+	if x >= 0 && y >= 0 {
+		rgba = textureLoad(t, vec2<i32>(x, y), 0);
+	}
 
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
+	if params.linear != 0u {
+		rgba = vec4(srgb_to_linear(rgba.rgb), rgba.a);
+	}
 
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
+	if params.premultiply != 0u {
+		rgba = vec4(rgba.rgb * rgba.a, rgba.a);
+	}
 
-	// normalization
-	rgb /= 9.;
-
-	return vec4(rgb, 1.);
+	return rgba;
 }
 
 @fragment
-fn fs_gaussian_blur(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
-	var rgb = vec3<f32>(0.);
-
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(-1, -1)).rgb;
-	rgb += 2. * textureSample(t, s, in.uv, vec2<i32>(0, -1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(1, -1)).rgb;
-
-	rgb += 2. * textureSample(t, s, in.uv, vec2<i32>(-1, 0)).rgb;
-	rgb += 4. * textureSample(t, s, in.uv, vec2<i32>(0, 0)).rgb;
-	rgb += 2. * textureSample(t, s, in.uv, vec2<i32>(1, 0)).rgb;
-
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(-1, 1)).rgb;
-	rgb += 2. * textureSample(t, s, in.uv, vec2<i32>(0, 1)).rgb;
-	rgb += 1. * textureSample(t, s, in.uv, vec2<i32>(1, 1)).rgb;
-
-	// normalization
-	rgb /= 16.;
-
-	return vec4(rgb, 1.);
-}
\ No newline at end of file
+fn fs_convolve(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+	let dimensions = vec2<i32>(textureDimensions(t));
+
+	// The fragment position is at the pixel center, truncating gives the pixel coordinate.
+	// When cropping, output pixel (0, 0) is centered on input pixel (radius, radius),
+	// else it is centered on input pixel (0, 0).
+	var top_left = vec2<i32>(in.position.xy);
+	if params.border_mode != BORDER_CROP {
+		top_left -= params.size / 2;
+	}
+
+	var rgba = vec4<f32>(0.);
+
+	for (var row = 0; row < params.size.y; row++) {
+		for (var col = 0; col < params.size.x; col++) {
+			let weight = weights[col + row * params.size.x];
+			rgba += weight * load(top_left + vec2<i32>(col, row), dimensions);
+		}
+	}
+
+	rgba = rgba * params.normalization + params.bias;
+
+	// Premultiplied colors were convolved along with alpha,
+	// so the convolved alpha is what they must be divided by.
+	if params.premultiply != 0u {
+		if rgba.a >= TRANSPARENT_ALPHA {
+			rgba = vec4(rgba.rgb / rgba.a, rgba.a);
+		} else {
+			rgba = vec4(vec3(0.), rgba.a);
+		}
+	}
+
+	if params.linear != 0u {
+		rgba = vec4(linear_to_srgb(rgba.rgb), rgba.a);
+	}
+
+	if params.alpha_mode == ALPHA_PASS_THROUGH {
+		rgba.a = load(top_left + params.size / 2, dimensions).a;
+	}
+
+	return rgba;
+}
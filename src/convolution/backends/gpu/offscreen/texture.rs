@@ -23,8 +23,11 @@ pub struct RenderTexture {
 
 impl RenderTexture {
     pub fn from_image(device: &wgpu::Device, img: &DynamicImage) -> Result<Self> {
-        let (width, height) = img.dimensions();
+        Self::new(device, img.dimensions())
+    }
 
+    /// A texture which can be rendered to, and read by a later render pass.
+    pub fn new(device: &wgpu::Device, (width, height): (u32, u32)) -> Result<Self> {
         let extent = wgpu::Extent3d {
             width,
             height,
@@ -38,7 +41,10 @@ impl RenderTexture {
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            // Pipelines bind the output of one stage as the input of the next.
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
             view_formats: &[],
         });
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -74,13 +80,14 @@ impl OutputBuffer {
 }
 
 pub struct DiffuseTexture {
+    #[allow(dead_code)] // Owned here such that it lives as long as its view.
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
 }
 
 impl DiffuseTexture {
-    /// Given a [`DynamicImage`], prepare a 
+    /// Given a [`DynamicImage`], prepare a texture which can be bound for
+    /// loading texels in a shader.
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -89,7 +96,12 @@ impl DiffuseTexture {
     ) -> Result<Self> {
         let (width, height) = img.dimensions();
 
-        let rgba = img.to_rgba8();
+        let rgba: Vec<u8> = img
+            .to_rgba32f()
+            .into_raw()
+            .into_iter()
+            .flat_map(f32::to_ne_bytes)
+            .collect();
 
         let size = wgpu::Extent3d {
             width,
@@ -119,30 +131,15 @@ impl DiffuseTexture {
             wgpu::ImageDataLayout {
                 offset: 0,
 
-                // Due to the Rgba8 format each pixel is 4 bytes wide.
-                bytes_per_row: Some(4 * width),
+                // Due to the Rgba32Float format each pixel is 16 bytes wide.
+                bytes_per_row: Some(16 * width),
                 rows_per_image: Some(height),
             },
             size,
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            // Use nearest everywhere.
-            // We don't want to affect results by doing filtering.
-            mag_filter: wgpu::FilterMode::Nearest,
-            min_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        Ok(Self {
-            texture,
-            view,
-            sampler,
-        })
+        Ok(Self { texture, view })
     }
 }
 
@@ -161,8 +158,8 @@ pub struct BufferDimensions {
 
 impl BufferDimensions {
     fn new(width: usize, height: usize) -> Self {
-        // RGBA spread out like [u8, u8, u8, u8], same size as a u32.
-        let bytes_per_pixel = std::mem::size_of::<u32>();
+        // RGBA spread out like [f32, f32, f32, f32].
+        let bytes_per_pixel = std::mem::size_of::<[f32; 4]>();
         let unpadded_bytes_per_row = width * bytes_per_pixel;
 
         // Right now, this number is 256 bytes.
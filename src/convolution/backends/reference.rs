@@ -0,0 +1,184 @@
+use image::{DynamicImage, GenericImageView, Rgba, Rgba32FImage};
+
+use crate::convolution::format;
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+use super::cpu::util::TRANSPARENT_ALPHA;
+
+/// A pixel with `f64` channels.
+type Pixel = [f64; 4];
+
+/// A deliberately simple convolution, which defines what the other backends should compute.
+///
+/// Each output pixel is computed on its own, reading every input pixel under the kernel
+/// with plain indexing and resolving the border mode per read, accumulating in `f64`.
+/// There is no padding, no unsafe code and no parallelism, so this is slow but easy to check.
+pub struct Reference {
+    input: DynamicImage,
+    kernel: KernelImpl,
+    options: Options,
+    output: Rgba32FImage,
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K)> for Reference {
+    fn from((input, kernel): (DynamicImage, K)) -> Self {
+        Self::from((input, kernel, Options::default()))
+    }
+}
+
+impl<K: Into<KernelImpl>> From<(DynamicImage, K, Options)> for Reference {
+    fn from((input, kernel, options): (DynamicImage, K, Options)) -> Self {
+        let kernel = kernel.into();
+        let (width, height) = options.output_dimensions(input.dimensions(), &kernel);
+
+        Self {
+            input,
+            kernel,
+            options,
+            output: Rgba32FImage::new(width, height),
+        }
+    }
+}
+
+impl Reference {
+    /// The input pixel at the given position, which may be past the edges,
+    /// in the color space the convolution is performed in.
+    fn read(&self, input: &Rgba32FImage, x: i64, y: i64) -> Pixel {
+        let (width, height) = input.dimensions();
+
+        let rgba = match (self.source_index(x, width), self.source_index(y, height)) {
+            (Some(x), Some(y)) => input.get_pixel(x, y).0,
+            _ => self.options.border_color,
+        };
+
+        let [mut red, mut green, mut blue, alpha] = rgba;
+        if self.options.color_space == ColorSpace::Linear {
+            red = format::srgb_to_linear(red);
+            green = format::srgb_to_linear(green);
+            blue = format::srgb_to_linear(blue);
+        }
+
+        let mut pixel = [red as f64, green as f64, blue as f64, alpha as f64];
+        if self.options.premultiply {
+            for channel in &mut pixel[..3] {
+                *channel *= alpha as f64;
+            }
+        }
+
+        pixel
+    }
+
+    /// Where the pixel at `index` along an axis of length `len` is read from,
+    /// or `None` for the border color.
+    ///
+    /// Spelled out step by step rather than using the index mapping of [`BorderMode`]
+    /// which the other backends share, such that a mistake there does not go unnoticed.
+    fn source_index(&self, index: i64, len: u32) -> Option<u32> {
+        let len = len as i64;
+        if len == 0 {
+            return None;
+        }
+
+        let mut index = index;
+        match self.options.border {
+            BorderMode::Clamp | BorderMode::Crop => index = index.max(0).min(len - 1),
+            // Reflect about the edge which was crossed, until within the image.
+            BorderMode::Mirror => {
+                while !(0..len).contains(&index) {
+                    index = if index < 0 {
+                        -index - 1
+                    } else {
+                        2 * len - 1 - index
+                    };
+                }
+            }
+            // Step a whole image towards it, until within the image.
+            BorderMode::Wrap => {
+                while !(0..len).contains(&index) {
+                    index += if index < 0 { len } else { -len };
+                }
+            }
+            BorderMode::Constant if !(0..len).contains(&index) => return None,
+            BorderMode::Constant => {}
+        }
+
+        Some(index as u32)
+    }
+
+    /// Convolve a single output pixel, where the top-left weight is over input pixel `(left, top)`.
+    fn convolve_pixel(&self, input: &Rgba32FImage, left: i64, top: i64) -> Pixel {
+        let kernel = &self.kernel;
+        let mut sum = [0.; 4];
+
+        for row in 0..kernel.height() {
+            for col in 0..kernel.width() {
+                let weight = kernel.weight(col, row) as f64;
+                let pixel = self.read(input, left + col as i64, top + row as i64);
+
+                for (sum, channel) in sum.iter_mut().zip(pixel) {
+                    *sum += weight * channel;
+                }
+            }
+        }
+
+        let normalization = kernel.normalization() as f64;
+        let bias = kernel.bias() as f64;
+        let mut pixel = sum.map(|sum| sum * normalization + bias);
+
+        if self.options.premultiply {
+            let alpha = pixel[3];
+            for channel in &mut pixel[..3] {
+                *channel = if alpha >= TRANSPARENT_ALPHA as f64 {
+                    *channel / alpha
+                } else {
+                    0.
+                };
+            }
+        }
+
+        pixel
+    }
+}
+
+impl ConvolveStrategy for Reference {
+    fn convolve(&mut self) -> Result<()> {
+        let input = self.input.to_rgba32f();
+
+        // When cropping, output pixel (0, 0) is centered on input pixel (radius_x, radius_y),
+        // else it is centered on input pixel (0, 0).
+        let (radius_x, radius_y) = self.kernel.radius();
+        let (center_x, center_y) = match self.options.border {
+            BorderMode::Crop => (radius_x as i64, radius_y as i64),
+            _ => (0, 0),
+        };
+
+        let mut output = std::mem::take(&mut self.output);
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            let (x, y) = (x as i64 + center_x, y as i64 + center_y);
+            let [red, green, blue, mut alpha] = self
+                .convolve_pixel(&input, x - radius_x as i64, y - radius_y as i64)
+                .map(|channel| channel as f32);
+
+            if self.options.alpha == AlphaMode::PassThrough {
+                alpha = self.read(&input, x, y)[3] as f32;
+            }
+
+            let mut rgba = [red, green, blue, alpha];
+            if self.options.color_space == ColorSpace::Linear {
+                for channel in &mut rgba[..3] {
+                    *channel = format::linear_to_srgb(*channel);
+                }
+            }
+
+            *pixel = Rgba(rgba);
+        }
+        self.output = output;
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<DynamicImage> {
+        Ok(format::restore(self.output, self.input.color()))
+    }
+}
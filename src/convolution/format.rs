@@ -0,0 +1,131 @@
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel, Rgba32FImage};
+
+/// Converts a convolved image back to the color type of the input image,
+/// such that e.g. 16 bit inputs give 16 bit outputs and
+/// inputs without an alpha channel give outputs without one.
+///
+/// Color types unknown to us are converted to 8 bit RGBA.
+pub(crate) fn restore(image: Rgba32FImage, color_type: ColorType) -> DynamicImage {
+    let image = DynamicImage::ImageRgba32F(image);
+
+    match color_type {
+        ColorType::L8 => image.to_luma8().into(),
+        ColorType::La8 => image.to_luma_alpha8().into(),
+        ColorType::Rgb8 => image.to_rgb8().into(),
+        ColorType::L16 => image.to_luma16().into(),
+        ColorType::La16 => image.to_luma_alpha16().into(),
+        ColorType::Rgb16 => image.to_rgb16().into(),
+        ColorType::Rgba16 => image.to_rgba16().into(),
+        ColorType::Rgb32F => image.to_rgb32f().into(),
+        ColorType::Rgba32F => image,
+        _ => image.to_rgba8().into(),
+    }
+}
+
+/// The color type [`restore`] converts to for the given input color type.
+pub(crate) fn restored_color_type(color_type: ColorType) -> ColorType {
+    match color_type {
+        ColorType::L8
+        | ColorType::La8
+        | ColorType::Rgb8
+        | ColorType::L16
+        | ColorType::La16
+        | ColorType::Rgb16
+        | ColorType::Rgba16
+        | ColorType::Rgb32F
+        | ColorType::Rgba32F => color_type,
+        _ => ColorType::Rgba8,
+    }
+}
+
+/// Copies the rows of `source` into `target`, starting at row `y`.
+///
+/// # Panics
+///
+/// If the images differ in color type or width, or the rows do not fit.
+pub(crate) fn copy_rows(target: &mut DynamicImage, source: &DynamicImage, y: u32) {
+    fn copy<P: Pixel>(
+        target: &mut ImageBuffer<P, Vec<P::Subpixel>>,
+        source: &ImageBuffer<P, Vec<P::Subpixel>>,
+        y: u32,
+    ) {
+        assert_eq!(target.width(), source.width());
+        let start = y as usize * target.width() as usize * P::CHANNEL_COUNT as usize;
+
+        let (target, source): (&mut [P::Subpixel], &[P::Subpixel]) = (target, source);
+        target[start..start + source.len()].copy_from_slice(source);
+    }
+
+    match (target, source) {
+        (DynamicImage::ImageLuma8(target), DynamicImage::ImageLuma8(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageLumaA8(target), DynamicImage::ImageLumaA8(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgb8(target), DynamicImage::ImageRgb8(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgba8(target), DynamicImage::ImageRgba8(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageLuma16(target), DynamicImage::ImageLuma16(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageLumaA16(target), DynamicImage::ImageLumaA16(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgb16(target), DynamicImage::ImageRgb16(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgba16(target), DynamicImage::ImageRgba16(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgb32F(target), DynamicImage::ImageRgb32F(source)) => {
+            copy(target, source, y)
+        }
+        (DynamicImage::ImageRgba32F(target), DynamicImage::ImageRgba32F(source)) => {
+            copy(target, source, y)
+        }
+        (target, source) => panic!(
+            "cannot copy {:?} rows into a {:?} image",
+            source.color(),
+            target.color()
+        ),
+    }
+}
+
+/// Decode a gamma encoded sRGB channel to linear light.
+#[inline(always)]
+pub(crate) fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Encode a linear light channel to gamma encoded sRGB.
+#[inline(always)]
+pub(crate) fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.003_130_8 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Applies `f` to the color channels of a floating point image, leaving alpha unchanged.
+///
+/// # Panics
+///
+/// If the image is not [`ColorType::Rgb32F`] or [`ColorType::Rgba32F`].
+pub(crate) fn map_colors(image: &mut DynamicImage, f: fn(f32) -> f32) {
+    match image {
+        DynamicImage::ImageRgb32F(image) => image.pixels_mut().for_each(|pixel| pixel.apply(f)),
+        DynamicImage::ImageRgba32F(image) => image
+            .pixels_mut()
+            .for_each(|pixel| pixel.apply_without_alpha(f)),
+        image => panic!("cannot map the colors of a {:?} image", image.color()),
+    }
+}
@@ -0,0 +1,56 @@
+use image::DynamicImage;
+
+use crate::{kernel::KernelImpl, prelude::*};
+
+/// Convolve images held in memory, without reading or writing files.
+///
+/// Implemented for anything which converts into a [`DynamicImage`],
+/// such as [`DynamicImage`] itself and [`image::ImageBuffer`]s of the common pixel types.
+///
+/// ```no_run
+/// use image_convolve::prelude::*;
+///
+/// # fn main() -> Result<()> {
+/// let image = image::RgbImage::new(64, 64);
+/// let blurred = image.convolve(Kernel::GaussianBlur, Backend::Auto, Options::default())?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait ConvolveImage {
+    /// Convolve the image with the kernel using the given backend, see [`Backend::convolve`].
+    fn convolve<K: Into<KernelImpl>>(
+        self,
+        kernel: K,
+        backend: Backend,
+        options: Options,
+    ) -> Result<DynamicImage>;
+
+    /// Convolve the image with each of the kernels in turn using the given backend,
+    /// see [`Backend::convolve_pipeline`].
+    fn convolve_pipeline<K: Into<KernelImpl>>(
+        self,
+        kernels: impl IntoIterator<Item = K>,
+        backend: Backend,
+        options: Options,
+    ) -> Result<DynamicImage>;
+}
+
+impl<I: Into<DynamicImage>> ConvolveImage for I {
+    fn convolve<K: Into<KernelImpl>>(
+        self,
+        kernel: K,
+        backend: Backend,
+        options: Options,
+    ) -> Result<DynamicImage> {
+        backend.convolve(self.into(), kernel, options)
+    }
+
+    fn convolve_pipeline<K: Into<KernelImpl>>(
+        self,
+        kernels: impl IntoIterator<Item = K>,
+        backend: Backend,
+        options: Options,
+    ) -> Result<DynamicImage> {
+        backend.convolve_pipeline(self.into(), kernels, options)
+    }
+}
@@ -1,19 +1,285 @@
 use clap::ValueEnum;
+use image::{DynamicImage, GenericImageView};
+use tracing::info;
+
+use crate::{kernel::KernelImpl, prelude::*};
+
+#[cfg(feature = "gpu")]
+use backends::gpu::offscreen::{context::GpuCtx, Offscreen};
+use backends::{
+    cpu::{self, tiled::Tiled},
+    reference::Reference,
+};
+use strategy::run;
 
 /// The various backends available, enumerated.
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum Backend {
+    /// Choose a CPU backend based on the kernel and image size, see [`Backend::choose`].
+    Auto,
+
     /// See [`backends::cpu::single`].
     SingleNestedLoops,
 
     /// See [`backends::cpu::single`].
     SingleNestedIterators,
 
+    /// See [`backends::cpu::simd`].
+    SingleSimd,
+
+    /// See [`backends::cpu::blocked`].
+    SingleBlocked,
+
     /// See [`backends::cpu::multi`].
+    #[cfg(feature = "rayon")]
     MultiRayon,
 
+    /// See [`backends::cpu::multi::Chunked`].
+    #[cfg(feature = "rayon")]
+    MultiChunked,
+
+    /// See [`backends::cpu::blocked`].
+    #[cfg(feature = "rayon")]
+    MultiBlocked,
+
+    /// See [`backends::cpu::separable`].
+    SingleSeparable,
+
+    /// See [`backends::cpu::separable`].
+    #[cfg(feature = "rayon")]
+    MultiSeparable,
+
+    /// See [`backends::cpu::fft`].
+    Fft,
+
+    /// See [`backends::cpu::summed_area`].
+    /// Only supports kernels whose weights are all equal, such as box blurs.
+    SummedArea,
+
+    /// See [`backends::cpu::fixed_point`].
+    /// Only supports 8 bit images in the sRGB color space.
+    FixedPoint,
+
     /// See [`backends::gpu::offscreen`].
+    #[cfg(feature = "gpu")]
     GpuOffscreen,
+
+    /// See [`backends::reference`].
+    /// Much slower than the others, meant for checking their results.
+    Reference,
+}
+
+impl Backend {
+    /// The fastest backend for separable kernels.
+    #[cfg(feature = "rayon")]
+    pub(crate) const SEPARABLE: Self = Backend::MultiSeparable;
+    #[cfg(not(feature = "rayon"))]
+    pub(crate) const SEPARABLE: Self = Backend::SingleSeparable;
+
+    /// The fastest backend for other kernels, unless they are large.
+    #[cfg(feature = "rayon")]
+    pub(crate) const DIRECT: Self = Backend::MultiRayon;
+    #[cfg(not(feature = "rayon"))]
+    pub(crate) const DIRECT: Self = Backend::SingleSimd;
+
+    /// Choose the CPU backend expected to be fastest for the given kernel and image dimensions.
+    ///
+    /// Separable kernels use [`Backend::MultiSeparable`], large kernels use [`Backend::Fft`]
+    /// (see [`backends::cpu::fft::is_preferable`]), and the rest use [`Backend::MultiRayon`].
+    /// Without the `rayon` feature, [`Backend::SingleSeparable`] and [`Backend::SingleSimd`]
+    /// are used instead.
+    pub fn choose(kernel: &KernelImpl, dimensions: (u32, u32)) -> Self {
+        if kernel.separate().is_some() {
+            Self::SEPARABLE
+        } else if backends::cpu::fft::is_preferable(kernel, dimensions) {
+            Backend::Fft
+        } else {
+            Self::DIRECT
+        }
+    }
+
+    /// Convolve an image in memory using this backend, returning the convolved image.
+    /// [`Backend::Auto`] is resolved using [`Backend::choose`].
+    ///
+    /// With a [`Options::memory_budget`] the image is convolved in strips,
+    /// see [`backends::cpu::tiled::Tiled`].
+    ///
+    /// See [`image_ext::ConvolveImage`] for a more convenient way of calling this.
+    ///
+    /// # Errors
+    ///
+    /// If the image is too small for the kernel, see [`Options::check_dimensions`],
+    /// or if the backend fails.
+    pub fn convolve<K: Into<KernelImpl>>(
+        self,
+        image: DynamicImage,
+        kernel: K,
+        options: Options,
+    ) -> Result<DynamicImage> {
+        let kernel = kernel.into();
+        options.check_dimensions(image.dimensions(), &kernel)?;
+
+        if let Some(memory_budget) = options.memory_budget {
+            return run(Tiled::new(image, kernel, options, self, memory_budget)?);
+        }
+
+        let backend = match self {
+            Backend::Auto => Backend::choose(&kernel, image.dimensions()),
+            backend => backend,
+        };
+        info!(?backend, "Backend");
+
+        match backend {
+            Backend::Auto => unreachable!("the automatic backend was resolved above"),
+            Backend::SingleNestedLoops => {
+                run(cpu::single::NestedLoops::from((image, kernel, options)))
+            }
+            Backend::SingleNestedIterators => {
+                run(cpu::single::NestedIterators::from((image, kernel, options)))
+            }
+            Backend::SingleSimd => run(cpu::simd::Simd::from((image, kernel, options))),
+            Backend::SingleBlocked => run(cpu::blocked::Single::from((image, kernel, options))),
+            #[cfg(feature = "rayon")]
+            Backend::MultiRayon => run(cpu::multi::NestedIterators::from((image, kernel, options))),
+            #[cfg(feature = "rayon")]
+            Backend::MultiChunked => run(cpu::multi::Chunked::from((image, kernel, options))),
+            #[cfg(feature = "rayon")]
+            Backend::MultiBlocked => run(cpu::blocked::Multi::from((image, kernel, options))),
+            Backend::SingleSeparable => {
+                run(cpu::separable::Single::try_from((image, kernel, options))?)
+            }
+            #[cfg(feature = "rayon")]
+            Backend::MultiSeparable => {
+                run(cpu::separable::Multi::try_from((image, kernel, options))?)
+            }
+            Backend::Fft => run(cpu::fft::Fft::from((image, kernel, options))),
+            Backend::SummedArea => run(cpu::summed_area::SummedArea::try_from((
+                image, kernel, options,
+            ))?),
+            Backend::FixedPoint => run(cpu::fixed_point::FixedPoint::try_from((
+                image, kernel, options,
+            ))?),
+            #[cfg(feature = "gpu")]
+            Backend::GpuOffscreen => run(Offscreen::new(GpuCtx::new(image)?, kernel, options)?),
+            Backend::Reference => run(Reference::from((image, kernel, options))),
+        }
+    }
+
+    /// Convolve an image in memory with each of the kernels in turn using this backend,
+    /// see [`Backend::convolve`].
+    ///
+    /// The stages pass floating point images between them, so nothing is quantized
+    /// until the result is converted back to the color type of the input at the end.
+    /// In [`ColorSpace::Linear`] the image is decoded once before the first stage
+    /// and encoded once after the last, rather than around every stage.
+    /// [`Backend::GpuOffscreen`] keeps the image on the GPU in between stages.
+    ///
+    /// [`Backend::SummedArea`] convolves a kernel repeated several times in a row as passes,
+    /// see [`backends::cpu::summed_area::SummedArea::with_passes`].
+    ///
+    /// A single stage gets the image as is, like [`Backend::convolve`].
+    /// [`Backend::FixedPoint`] only supports 8 bit images, so its stages pass those instead,
+    /// rounding after each stage.
+    /// An empty pipeline returns the image unchanged.
+    ///
+    /// # Errors
+    ///
+    /// If [`Options::memory_budget`] is given along with more than one kernel, since the images
+    /// in between the stages would be held whole, or if any stage fails, see [`Backend::convolve`].
+    pub fn convolve_pipeline<K: Into<KernelImpl>>(
+        self,
+        image: DynamicImage,
+        kernels: impl IntoIterator<Item = K>,
+        options: Options,
+    ) -> Result<DynamicImage> {
+        let mut kernels: Vec<KernelImpl> = kernels.into_iter().map(Into::into).collect();
+        match kernels.len() {
+            0 => return Ok(image),
+            1 => return self.convolve(image, kernels.remove(0), options),
+            _ if options.memory_budget.is_some() => {
+                return Err(Error::UnsupportedBackend(format!(
+                    "a memory budget only supports a single kernel, got {}",
+                    kernels.len()
+                )));
+            }
+            _ if self == Backend::FixedPoint => {
+                return kernels
+                    .into_iter()
+                    .try_fold(image, |image, kernel| self.convolve(image, kernel, options));
+            }
+            _ => {}
+        }
+
+        let color_type = image.color();
+
+        // Images without alpha stay without alpha in between stages,
+        // such that a convolved alpha channel cannot affect later stages.
+        let mut image = if color_type.has_alpha() {
+            image.into_rgba32f().into()
+        } else {
+            image.into_rgb32f().into()
+        };
+
+        // The stages convolve linear values as they are, so only the ends convert.
+        let mut stage_options = options;
+        if options.color_space == ColorSpace::Linear {
+            format::map_colors(&mut image, format::srgb_to_linear);
+            stage_options.color_space = ColorSpace::Srgb;
+            stage_options.border_color[..3]
+                .iter_mut()
+                .for_each(|channel| *channel = format::srgb_to_linear(*channel));
+        }
+
+        let mut image = match self {
+            #[cfg(feature = "gpu")]
+            Backend::GpuOffscreen => {
+                info!(stages = kernels.len(), "Pipeline on the GPU");
+                run(Offscreen::pipeline(
+                    GpuCtx::new(image)?,
+                    kernels,
+                    stage_options,
+                )?)?
+            }
+            Backend::SummedArea => {
+                for (stage, (kernel, passes)) in repeats(kernels).into_iter().enumerate() {
+                    info!(stage, passes, "Pipeline stage");
+                    let summed_area =
+                        cpu::summed_area::SummedArea::try_from((image, kernel, stage_options))?;
+                    image = run(summed_area.with_passes(passes))?;
+                }
+
+                image
+            }
+            _ => {
+                for (stage, kernel) in kernels.into_iter().enumerate() {
+                    info!(stage, "Pipeline stage");
+                    image = self.convolve(image, kernel, stage_options)?;
+                }
+
+                image
+            }
+        };
+
+        if options.color_space == ColorSpace::Linear {
+            format::map_colors(&mut image, format::linear_to_srgb);
+        }
+
+        Ok(format::restore(image.into_rgba32f(), color_type))
+    }
+}
+
+/// Each kernel along with how many times in a row it is repeated.
+fn repeats(kernels: Vec<KernelImpl>) -> Vec<(KernelImpl, u32)> {
+    let mut repeats: Vec<(KernelImpl, u32)> = vec![];
+
+    for kernel in kernels {
+        match repeats.last_mut() {
+            Some((last, count)) if *last == kernel => *count += 1,
+            _ => repeats.push((kernel, 1)),
+        }
+    }
+
+    repeats
 }
 
 /// Implementors of the [`strategy::ConvolveStrategy`]
@@ -23,20 +289,55 @@ pub mod backends {
         /// Single threaded.
         pub mod single;
 
+        /// Single threaded, using explicit SIMD instructions.
+        pub mod simd;
+
+        /// In cache sized 2D blocks, single- or multi threaded.
+        pub mod blocked;
+
         /// Multi threaded.
+        #[cfg(feature = "rayon")]
         pub mod multi;
 
+        /// Two 1D passes for separable kernels, single- or multi threaded.
+        pub mod separable;
+
+        /// In the frequency domain, multi threaded with the `rayon` feature.
+        pub mod fft;
+
+        /// Box blurs of any size via a summed area table.
+        pub mod summed_area;
+
+        /// Integer arithmetic on 8 bit images.
+        pub mod fixed_point;
+
+        /// Any of the direct backends, in strips of bounded memory.
+        pub mod tiled;
+
         /// Common CPU operations.
         pub(crate) mod util;
     }
 
     /// GPU based convolution.
+    #[cfg(feature = "gpu")]
     pub mod gpu {
         /// Convolution via an offscreen GPU pipeline.
         pub mod offscreen;
     }
+
+    /// A simple and slow convolution defining the expected results of the other backends.
+    pub mod reference;
 }
 
+/// Options common to all backends, such as border handling.
+pub mod options;
+
+/// Convolution of images held in memory.
+pub mod image_ext;
+
+/// Conversion between the pixel formats and color spaces of images and backends.
+pub(crate) mod format;
+
 /// Holds the common trait for backends,
 /// as well as the strategy implementation.
 pub mod strategy;
@@ -0,0 +1,191 @@
+use clap::ValueEnum;
+
+use crate::kernel::KernelImpl;
+use crate::prelude::*;
+
+/// How pixels outside the image are treated when the kernel
+/// reaches past the edges.
+///
+/// Each mode is shown for a row `abcd` extended by two pixels on each side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum BorderMode {
+    /// Repeat the edge pixel: `aa|abcd|dd`.
+    #[default]
+    Clamp,
+
+    /// Reflect about the edge, including the edge pixel: `ba|abcd|dc`.
+    Mirror,
+
+    /// Wrap around to the opposite edge: `cd|abcd|ab`.
+    Wrap,
+
+    /// Use a constant color, see [`Options::border_color`]: `xx|abcd|xx`.
+    Constant,
+
+    /// Only produce output where the kernel fits entirely within the image.
+    /// The output is smaller than the input by the kernel size minus one in each dimension.
+    Crop,
+}
+
+impl BorderMode {
+    /// Maps a possibly out of bounds index along an axis of length `len`
+    /// to the index of the pixel which should be read instead.
+    ///
+    /// Returns `None` if the border color should be used, and for an empty axis,
+    /// which has no pixel to read.
+    /// Otherwise never returns `None` for [`BorderMode::Crop`], since cropping means
+    /// indices are never out of bounds.
+    #[inline(always)]
+    pub(crate) fn source_index(self, index: i64, len: u32) -> Option<u32> {
+        if len == 0 {
+            return None;
+        }
+        let len = len as i64;
+
+        let index = match self {
+            BorderMode::Clamp | BorderMode::Crop => index.clamp(0, len - 1),
+            BorderMode::Mirror => {
+                let period = 2 * len;
+                let index = index.rem_euclid(period);
+                if index < len {
+                    index
+                } else {
+                    period - 1 - index
+                }
+            }
+            BorderMode::Wrap => index.rem_euclid(len),
+            BorderMode::Constant => {
+                if !(0..len).contains(&index) {
+                    return None;
+                }
+                index
+            }
+        };
+
+        Some(index as u32)
+    }
+}
+
+/// How the alpha channel is treated.
+/// Images without an alpha channel are treated as opaque, and
+/// the output will not have an alpha channel either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum AlphaMode {
+    /// Keep the alpha of the input pixel untouched.
+    #[default]
+    PassThrough,
+
+    /// Convolve the alpha channel like the color channels.
+    Convolve,
+}
+
+/// Which color space the convolution is performed in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorSpace {
+    /// Convolve the stored, gamma encoded sRGB values directly.
+    /// This is cheaper, but e.g. blurs look too dark since the values are not proportional to light.
+    Srgb,
+
+    /// Decode sRGB values to linear light before convolving, and encode them afterwards.
+    /// The inputs are assumed to be sRGB encoded, and so is the border color.
+    #[default]
+    Linear,
+}
+
+/// Options controlling how a convolution is performed.
+/// These are common to all backends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Options {
+    /// How to handle pixels past the edges of the image.
+    pub border: BorderMode,
+
+    /// The RGBA color used for [`BorderMode::Constant`], channels in `0.0..=1.0`.
+    pub border_color: [f32; 4],
+
+    /// How to treat the alpha channel.
+    pub alpha: AlphaMode,
+
+    /// Multiply the color channels by alpha before convolving, and divide by the
+    /// convolved alpha afterwards.
+    ///
+    /// This avoids the colors of transparent pixels bleeding into their neighbours,
+    /// which otherwise shows up as e.g. dark fringes when blurring around transparent edges.
+    /// Pixels which end up fully transparent get black color channels.
+    pub premultiply: bool,
+
+    /// The color space to convolve in.
+    pub color_space: ColorSpace,
+
+    /// Convolve in strips of rows, keeping the floating point working buffers of the backend
+    /// within about this many bytes, see [`crate::convolution::backends::cpu::tiled`].
+    ///
+    /// This does not bound the total memory used: the input and output images are still
+    /// held whole in the color type of the input, so the peak is about both images plus the budget.
+    ///
+    /// Only used by [`crate::convolution::Backend::convolve`], which then
+    /// only supports the direct CPU backends.
+    /// [`crate::convolution::Backend::convolve_pipeline`] rejects it for more than one kernel.
+    pub memory_budget: Option<usize>,
+}
+
+impl Options {
+    /// Check that an image of the given dimensions can be convolved with the given kernel.
+    ///
+    /// Every border mode except [`BorderMode::Crop`] defines the pixels past the edges,
+    /// so any non-empty image works, even a single pixel.
+    /// Cropping needs the image to be at least as large as the kernel.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::ImageTooSmall`] if the image is empty, or smaller than the kernel when cropping.
+    pub fn check_dimensions(&self, (width, height): (u32, u32), kernel: &KernelImpl) -> Result<()> {
+        let (min_width, min_height) = match self.border {
+            BorderMode::Crop => (kernel.width(), kernel.height()),
+            _ => (1, 1),
+        };
+
+        if width < min_width || height < min_height {
+            return Err(Error::ImageTooSmall {
+                width,
+                height,
+                kernel: (kernel.width(), kernel.height()),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The dimensions of the output image when convolving an image of the given
+    /// dimensions with the given kernel.
+    ///
+    /// When cropping an image smaller than the kernel, the output is empty,
+    /// see [`Options::check_dimensions`].
+    pub fn output_dimensions(
+        &self,
+        (width, height): (u32, u32),
+        kernel: &KernelImpl,
+    ) -> (u32, u32) {
+        let (radius_x, radius_y) = kernel.radius();
+
+        match self.border {
+            BorderMode::Crop => (
+                width.saturating_sub(2 * radius_x),
+                height.saturating_sub(2 * radius_y),
+            ),
+            _ => (width, height),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            border: BorderMode::default(),
+            border_color: [0., 0., 0., 1.],
+            alpha: AlphaMode::default(),
+            premultiply: false,
+            color_space: ColorSpace::default(),
+            memory_budget: None,
+        }
+    }
+}
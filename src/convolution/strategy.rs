@@ -1,7 +1,10 @@
-use std::path::Path;
+use std::{
+    io::{Cursor, Write},
+    path::Path,
+};
 
-use image::DynamicImage;
-use tracing::info;
+use image::{DynamicImage, ImageError, ImageFormat, ImageResult};
+use tracing::{info, warn};
 
 use crate::prelude::*;
 
@@ -23,19 +26,92 @@ pub fn prepare<P: AsRef<Path>>(input: P) -> Result<DynamicImage> {
     Ok(image::io::Reader::open(input)?.decode()?)
 }
 
-/// Convolve the input file by using the given backend.
+/// Convolve the input file by using the given backend, and save the result to the output path.
 pub fn convolve<Backend: ConvolveStrategy, P: AsRef<Path>>(
-    mut backend: Backend,
+    backend: Backend,
     output: P,
 ) -> Result<()> {
+    let image_output = run(backend)?;
+
+    save(&image_output, output)
+}
+
+/// Convolve by using the given backend, returning the resulting image.
+pub fn run<Backend: ConvolveStrategy>(mut backend: Backend) -> Result<DynamicImage> {
     info!("Executing convolution");
     backend.convolve()?;
 
     info!("Finishing");
-    let image_output = backend.finish()?.to_rgb8();
+    backend.finish()
+}
+
+/// Decode an image held in memory, such as one read from stdin.
+/// The format is guessed from the contents.
+pub fn decode(bytes: &[u8]) -> Result<DynamicImage> {
+    Ok(image::io::Reader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .decode()?)
+}
+
+/// Save the image, keeping its color type if the output format supports it.
+/// Otherwise it is converted to 16 bits and then 8 bits per channel until the format supports it.
+pub fn save<P: AsRef<Path>>(image: &DynamicImage, output: P) -> Result<()> {
+    let output = output.as_ref();
 
     info!("Saving result");
-    image_output.save(output)?;
+    convert_until_supported(image, |image| image.save(output))
+}
+
+/// Save the image in the given format regardless of the file extension, see [`save`].
+pub fn save_with_format<P: AsRef<Path>>(
+    image: &DynamicImage,
+    output: P,
+    format: ImageFormat,
+) -> Result<()> {
+    let output = output.as_ref();
+
+    info!(?format, "Saving result");
+    convert_until_supported(image, |image| image.save_with_format(output, format))
+}
+
+/// Encode the image in the given format and write it to the writer, such as stdout.
+/// The color type is converted if needed, see [`save`].
+pub fn write_to<W: Write>(image: &DynamicImage, writer: &mut W, format: ImageFormat) -> Result<()> {
+    info!(?format, "Writing result");
+
+    // Some encoders need to seek, which e.g. stdout cannot.
+    let bytes = convert_until_supported(image, |image| {
+        let mut bytes = Cursor::new(vec![]);
+        image.write_to(&mut bytes, format)?;
+        Ok(bytes.into_inner())
+    })?;
+
+    Ok(writer.write_all(&bytes)?)
+}
+
+/// Run the encoding operation, converting the image to 16 bits and then 8 bits per channel
+/// as long as the format does not support its color type.
+fn convert_until_supported<T>(
+    image: &DynamicImage,
+    mut encode: impl FnMut(&DynamicImage) -> ImageResult<T>,
+) -> Result<T> {
+    let mut result = encode(image);
+
+    for bits in [16, 8] {
+        let Err(ImageError::Unsupported(e)) = &result else {
+            break;
+        };
+
+        let converted: DynamicImage = match (bits, image.color().has_alpha()) {
+            (16, true) => image.to_rgba16().into(),
+            (16, false) => image.to_rgb16().into(),
+            (_, true) => image.to_rgba8().into(),
+            (_, false) => image.to_rgb8().into(),
+        };
+
+        warn!(%e, color_type = ?converted.color(), "Converting output");
+        result = encode(&converted);
+    }
 
-    Ok(())
+    Ok(result?)
 }
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use thiserror::Error;
 
 /// Re-export of [`std::result::Result`] but using our own [`enum@Error`].
@@ -7,10 +9,73 @@ pub type Result<T> = std::result::Result<T, Error>;
 /// Errors that may occur in this library.
 #[derive(Debug, Error)]
 pub enum Error {
-    /// GPU related error.
+    /// GPU related error, such as a validation error.
     #[error("GPU error: {0}")]
     Gpu(String),
 
+    /// No GPU adapter is available, e.g. because there is no GPU or driver.
+    #[error("No GPU adapter available")]
+    AdapterUnavailable,
+
+    /// The GPU buffer holding the result could not be mapped for reading,
+    /// e.g. because the device was lost.
+    #[error("Failed to map GPU buffer: {0}")]
+    BufferMapFailed(String),
+
+    /// A kernel could not be constructed.
+    #[error("Invalid kernel: {0}")]
+    InvalidKernel(String),
+
+    /// The kernel cannot be used with the chosen backend.
+    #[error("Unsupported kernel: {0}")]
+    UnsupportedKernel(String),
+
+    /// The chosen backend does not support what was asked of it.
+    #[error("Unsupported backend: {0}")]
+    UnsupportedBackend(String),
+
+    /// The image is too small to be convolved with the kernel, see [`crate::prelude::Options::check_dimensions`].
+    #[error("A {width}x{height} image is too small for a {}x{} kernel", kernel.0, kernel.1)]
+    ImageTooSmall {
+        /// The width of the image.
+        width: u32,
+        /// The height of the image.
+        height: u32,
+        /// The width and height of the kernel.
+        kernel: (u32, u32),
+    },
+
+    /// A kernel description could not be parsed.
+    /// Lines and columns start at 1.
+    #[error("Kernel parse error at line {line}, column {column}: {message}")]
+    KernelParse {
+        /// The line where the error occured.
+        line: usize,
+        /// The column where the error occured.
+        column: usize,
+        /// What went wrong.
+        message: String,
+    },
+
+    /// The inputs to process could not be determined, e.g. because of an invalid glob pattern.
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    /// Some of the images of a batch could not be processed.
+    /// The errors of the individual images are also logged as they happen.
+    #[error("{} of {total} images failed:{}", failures.len(), list_failures(failures))]
+    Batch {
+        /// The input paths of the images which failed, along with why.
+        failures: Vec<(PathBuf, Error)>,
+        /// The number of images in the batch.
+        total: usize,
+    },
+
+    /// A thread pool could not be created.
+    #[cfg(feature = "rayon")]
+    #[error("Thread pool error: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
     /// IO transparent error.
     #[error("IO error: {0}")]
     IO(#[from] std::io::Error),
@@ -19,3 +84,11 @@ pub enum Error {
     #[error("Image library error: {0}")]
     Image(#[from] image::ImageError),
 }
+
+/// One line per failed image of a batch, see [`Error::Batch`].
+fn list_failures(failures: &[(PathBuf, Error)]) -> String {
+    failures
+        .iter()
+        .map(|(path, e)| format!("\n  {}: {e}", path.display()))
+        .collect()
+}
@@ -1,10 +1,14 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::Path};
 
 use clap::ValueEnum;
 
+use crate::prelude::*;
+
+mod parse;
+
 /// Pre-defined kernels.
 /// See [Wikipedia](https://en.wikipedia.org/wiki/Kernel_(image_processing)).
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum Kernel {
     /// The identity operation.
     Identity,
@@ -19,9 +23,12 @@ pub enum Kernel {
     Sharpen,
 
     /// Box blur.
+    /// See [`KernelImpl::box_blur`] for a box blur of any size.
     BoxBlur,
 
     /// Gaussian blur.
+    /// See [`KernelImpl::gaussian`] for a Gaussian blur of any size.
+    #[value(alias = "gaussian")]
     GaussianBlur,
 }
 
@@ -32,21 +39,280 @@ impl Display for Kernel {
 }
 
 /// A kernel with its associated weights an normalization factor.
-#[derive(Debug, Copy, Clone)]
+///
+/// Kernels may have any odd width and height, such that there is
+/// always a center weight to place over the output pixel.
+///
+/// Each output channel is computed as the weighted sum of the neighbourhood,
+/// multiplied by the normalization, plus the bias.
+#[derive(Debug, Clone, PartialEq)]
 pub struct KernelImpl {
-    /// Weights from top-left to bottom-right.
-    pub weights: [f32; 9],
+    width: u32,
+    height: u32,
+
+    /// Weights from top-left to bottom-right, row by row.
+    weights: Vec<f32>,
 
     /// Normalization.
-    pub normalization: f32,
+    normalization: f32,
+
+    /// Added to each channel after normalization.
+    bias: f32,
 }
 
 impl From<Kernel> for KernelImpl {
     fn from(kernel: Kernel) -> Self {
         Self {
-            weights: *kernel.matrix(),
+            width: 3,
+            height: 3,
+            weights: kernel.matrix().to_vec(),
             normalization: kernel.normalization(),
+            bias: 0.,
+        }
+    }
+}
+
+impl KernelImpl {
+    /// Create a kernel of the given size.
+    /// The weights are given row by row, from top-left to bottom-right.
+    ///
+    /// # Errors
+    ///
+    /// If the width or height is not odd, or if the number of weights
+    /// does not match the size.
+    pub fn new(width: u32, height: u32, weights: Vec<f32>, normalization: f32) -> Result<Self> {
+        if width.is_multiple_of(2) || height.is_multiple_of(2) {
+            return Err(Error::InvalidKernel(format!(
+                "kernel size must be odd, got {width}x{height}"
+            )));
+        }
+
+        let len = width.checked_mul(height).ok_or_else(|| {
+            Error::InvalidKernel(format!("a {width}x{height} kernel has too many weights"))
+        })?;
+        if weights.len() != len as usize {
+            return Err(Error::InvalidKernel(format!(
+                "a {width}x{height} kernel needs {len} weights, got {}",
+                weights.len()
+            )));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            weights,
+            normalization,
+            bias: 0.,
+        })
+    }
+
+    /// The largest width and height of [`KernelImpl::box_blur`], a kernel of up to 4097x4097 weights.
+    pub const MAX_BOX_SIZE: u32 = 4097;
+
+    /// Create a normalized box blur kernel of the given size, whose weights are all equal.
+    ///
+    /// Box blurs of any size are convolved at the same cost per pixel by
+    /// [`crate::convolution::backends::cpu::summed_area`].
+    ///
+    /// # Errors
+    ///
+    /// If the width or height is not odd, or if either is larger than
+    /// [`KernelImpl::MAX_BOX_SIZE`].
+    pub fn box_blur(width: u32, height: u32) -> Result<Self> {
+        if width > Self::MAX_BOX_SIZE || height > Self::MAX_BOX_SIZE {
+            return Err(Error::InvalidKernel(format!(
+                "box blur size must be at most {0}x{0}, got {width}x{height}",
+                Self::MAX_BOX_SIZE
+            )));
+        }
+
+        let weights = vec![1.; width as usize * height as usize];
+
+        Self::new(width, height, weights, 1. / (width as f32 * height as f32))
+    }
+
+    /// The largest radius of [`KernelImpl::gaussian`], a kernel of 2049x2049 weights.
+    pub const MAX_GAUSSIAN_RADIUS: u32 = 1024;
+
+    /// Create a normalized Gaussian blur kernel with the given standard deviation.
+    ///
+    /// The radius defaults to `ceil(3 * sigma)`, which covers more than 99% of
+    /// the distribution. The weights are normalized such that they sum to one.
+    ///
+    /// # Errors
+    ///
+    /// If sigma is not a positive, finite number, or if the radius is larger than
+    /// [`KernelImpl::MAX_GAUSSIAN_RADIUS`].
+    pub fn gaussian(sigma: f32, radius: Option<u32>) -> Result<Self> {
+        if !(sigma.is_finite() && sigma > 0.) {
+            return Err(Error::InvalidKernel(format!(
+                "Gaussian sigma must be positive, got {sigma}"
+            )));
+        }
+
+        // Computed in `f64`, since the cast saturates rather than overflows for large sigmas.
+        let radius = radius.unwrap_or_else(|| (3. * sigma as f64).ceil() as u32);
+        let size = radius
+            .checked_mul(2)
+            .and_then(|diameter| diameter.checked_add(1))
+            .filter(|_| radius <= Self::MAX_GAUSSIAN_RADIUS)
+            .ok_or_else(|| {
+                Error::InvalidKernel(format!(
+                    "Gaussian radius must be at most {}, got {radius}",
+                    Self::MAX_GAUSSIAN_RADIUS
+                ))
+            })?;
+
+        let weights: Vec<f32> = (0..size)
+            .flat_map(|row| (0..size).map(move |col| (col, row)))
+            .map(|(col, row)| {
+                let x = col as f32 - radius as f32;
+                let y = row as f32 - radius as f32;
+
+                (-(x * x + y * y) / (2. * sigma * sigma)).exp()
+            })
+            .collect();
+        let sum: f32 = weights.iter().sum();
+
+        Self::new(size, size, weights, 1. / sum)
+    }
+
+    /// Decompose the kernel into a horizontal and a vertical 1D kernel,
+    /// if possible.
+    ///
+    /// A kernel is separable if its weight matrix has rank one, i.e. it is the outer product
+    /// of a column vector and a row vector. Convolving with the row vector (the horizontal kernel)
+    /// and then the column vector (the vertical kernel) gives the same result as convolving
+    /// with the full kernel, at `O(width + height)` instead of `O(width * height)` per pixel.
+    ///
+    /// Normalization and bias are not part of the decomposition.
+    ///
+    /// Returns `None` if the kernel is not separable.
+    pub fn separate(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        // The largest weight is used as the pivot, which keeps the division below well conditioned.
+        let (pivot_index, pivot) = self
+            .weights
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.abs().total_cmp(&b.abs()))?;
+
+        let (pivot_col, pivot_row) = (
+            pivot_index as u32 % self.width,
+            pivot_index as u32 / self.width,
+        );
+
+        let horizontal: Vec<f32> = (0..self.width)
+            .map(|col| self.weight(col, pivot_row))
+            .collect();
+
+        if pivot == 0. {
+            return Some((horizontal, vec![0.; self.height as usize]));
         }
+
+        let vertical: Vec<f32> = (0..self.height)
+            .map(|row| self.weight(pivot_col, row) / pivot)
+            .collect();
+
+        let tolerance = pivot.abs() * 1e-5;
+        let is_rank_one = (0..self.height).all(|row| {
+            (0..self.width).all(|col| {
+                (self.weight(col, row) - vertical[row as usize] * horizontal[col as usize]).abs()
+                    <= tolerance
+            })
+        });
+
+        is_rank_one.then_some((horizontal, vertical))
+    }
+
+    /// Combine two kernels into one, such that convolving with the result is equivalent to
+    /// convolving with `self` and then with `next`.
+    ///
+    /// The weights of the result are the convolution of the two weight matrices,
+    /// so it is as large as both kernels' radii combined. For example the
+    /// Gaussian blur composed with the Laplacian edge detection is the Laplacian of Gaussian.
+    ///
+    /// The results only match in the interior of the image, where neither pass reads past
+    /// the edges. Near the edges the border mode is applied once instead of twice.
+    pub fn compose(&self, next: &KernelImpl) -> KernelImpl {
+        let width = self.width + next.width - 1;
+        let height = self.height + next.height - 1;
+        let mut weights = vec![0.; (width * height) as usize];
+
+        for row in 0..self.height {
+            for col in 0..self.width {
+                let weight = self.weight(col, row);
+
+                for next_row in 0..next.height {
+                    for next_col in 0..next.width {
+                        let index = (col + next_col) + (row + next_row) * width;
+                        weights[index as usize] += weight * next.weight(next_col, next_row);
+                    }
+                }
+            }
+        }
+
+        // The bias of the first pass is a constant, which the second pass
+        // weighs by its sum of weights like any other constant image.
+        let next_sum: f32 = next.weights.iter().sum();
+
+        KernelImpl {
+            width,
+            height,
+            weights,
+            normalization: self.normalization * next.normalization,
+            bias: self.bias * next_sum * next.normalization + next.bias,
+        }
+    }
+
+    /// Set the bias, which is added to each channel after normalization.
+    /// Channels are in the `0.0..=1.0` range, so a bias of `0.5` shifts
+    /// results to mid-gray, which is useful for e.g. edge detection or embossing.
+    pub fn with_bias(self, bias: f32) -> Self {
+        Self { bias, ..self }
+    }
+
+    /// Load a kernel from a text file.
+    /// See [`KernelImpl::from_str`] for the expected format.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// The width of the kernel.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height of the kernel.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of pixels on each side of the center pixel, horizontally and vertically.
+    /// A 3x3 kernel has radius `(1, 1)`, a 7x5 kernel has radius `(3, 2)`.
+    pub fn radius(&self) -> (u32, u32) {
+        (self.width / 2, self.height / 2)
+    }
+
+    /// Weights from top-left to bottom-right, row by row.
+    pub fn weights(&self) -> &[f32] {
+        &self.weights
+    }
+
+    /// The weight at the given position within the kernel.
+    #[inline(always)]
+    pub fn weight(&self, column: u32, row: u32) -> f32 {
+        self.weights[(column + row * self.width) as usize]
+    }
+
+    /// Normalization.
+    pub fn normalization(&self) -> f32 {
+        self.normalization
+    }
+
+    /// Bias.
+    pub fn bias(&self) -> f32 {
+        self.bias
     }
 }
 
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+
+use crate::prelude::*;
+
+use super::KernelImpl;
+
+/// Parses a kernel from a plain-text description.
+///
+/// Each non-empty line is either a row of weights or a setting.
+/// Weights are separated by whitespace or commas, and all rows must have the same, odd length.
+/// The number of rows must be odd as well.
+/// Settings are a keyword followed by a number:
+///
+/// * `divisor <n>`: normalize by dividing by `n`
+/// * `normalization <n>`: normalize by multiplying by `n`
+/// * `bias <n>`: added after normalization, see [`KernelImpl::with_bias`]
+///
+/// Everything after a `#` is a comment.
+///
+/// ```norust
+/// # 5x5 Gaussian blur
+/// divisor 256
+///
+/// 1  4  6  4 1
+/// 4 16 24 16 4
+/// 6 24 36 24 6
+/// 4 16 24 16 4
+/// 1  4  6  4 1
+/// ```
+impl FromStr for KernelImpl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut weights = vec![];
+        let mut rows = 0u32;
+        let mut width = None;
+        let mut normalization = None;
+        let mut bias = None;
+        // The line and column of the first and the last row, to report even sizes.
+        let mut first_row = None;
+        let mut last_row = (0, 0);
+
+        for (line_index, line) in s.lines().enumerate() {
+            let line_number = line_index + 1;
+            let line = line.split('#').next().unwrap_or_default();
+
+            let mut tokens = tokens(line).peekable();
+            let Some(&(first_column, first)) = tokens.peek() else {
+                continue;
+            };
+
+            let setting = match first.trim_end_matches(':') {
+                "divisor" => Some((&mut normalization, true)),
+                "normalization" => Some((&mut normalization, false)),
+                "bias" => Some((&mut bias, false)),
+                _ => None,
+            };
+
+            if let Some((value, is_divisor)) = setting {
+                tokens.next();
+
+                if value.is_some() {
+                    return Err(parse_error(
+                        line_number,
+                        first_column,
+                        format!("`{first}` given more than once"),
+                    ));
+                }
+
+                let Some((column, token)) = tokens.next() else {
+                    return Err(parse_error(
+                        line_number,
+                        first_column + first.chars().count(),
+                        format!("`{first}` needs a value"),
+                    ));
+                };
+                let number = parse_number(line_number, column, token)?;
+
+                if let Some((column, token)) = tokens.next() {
+                    return Err(parse_error(
+                        line_number,
+                        column,
+                        format!("unexpected `{token}` after `{first}` value"),
+                    ));
+                }
+
+                if is_divisor {
+                    if number == 0. {
+                        return Err(parse_error(line_number, column, "divisor cannot be zero"));
+                    }
+                    *value = Some(1. / number);
+                } else {
+                    *value = Some(number);
+                }
+
+                continue;
+            }
+
+            let row_start = weights.len();
+            for (column, token) in tokens {
+                weights.push(parse_number(line_number, column, token)?);
+            }
+            let row_width = weights.len() - row_start;
+
+            match width {
+                None => width = Some(row_width),
+                Some(width) if width != row_width => {
+                    return Err(parse_error(
+                        line_number,
+                        first_column,
+                        format!("row has {row_width} weights, but previous rows have {width}"),
+                    ));
+                }
+                Some(_) => {}
+            }
+            first_row.get_or_insert((line_number, first_column));
+            last_row = (line_number, first_column);
+            rows += 1;
+        }
+
+        let Some(width) = width else {
+            return Err(parse_error(
+                s.lines().count().max(1),
+                1,
+                "no kernel weights given",
+            ));
+        };
+
+        if width.is_multiple_of(2) {
+            let (line, column) = first_row.unwrap_or_default();
+            return Err(parse_error(
+                line,
+                column,
+                format!("rows have {width} weights, but the kernel width must be odd"),
+            ));
+        }
+        if rows.is_multiple_of(2) {
+            let (line, column) = last_row;
+            return Err(parse_error(
+                line,
+                column,
+                format!("kernel has {rows} rows, but the kernel height must be odd"),
+            ));
+        }
+
+        Ok(
+            Self::new(width as u32, rows, weights, normalization.unwrap_or(1.))?
+                .with_bias(bias.unwrap_or(0.)),
+        )
+    }
+}
+
+/// Splits a line into tokens separated by whitespace and commas,
+/// along with the (1-based) column each token starts at.
+/// Columns count characters rather than bytes, such that they match what an editor shows.
+fn tokens(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(|c: char| c.is_whitespace() || c == ',')
+        .scan(0, |offset, token| {
+            let column = *offset + 1;
+            *offset += token.chars().count() + 1;
+            Some((column, token))
+        })
+        .filter(|(_, token)| !token.is_empty())
+}
+
+fn parse_number(line: usize, column: usize, token: &str) -> Result<f32> {
+    token
+        .parse()
+        .map_err(|_| parse_error(line, column, format!("`{token}` is not a number")))
+}
+
+fn parse_error(line: usize, column: usize, message: impl Into<String>) -> Error {
+    Error::KernelParse {
+        line,
+        column,
+        message: message.into(),
+    }
+}
@@ -14,3 +14,6 @@ pub mod error;
 
 /// Image convolution.
 pub mod convolution;
+
+/// Processing many images in a single run.
+pub mod batch;
@@ -1,13 +1,6 @@
 use clap::Parser;
 use image_convolve::{
-    convolution::{
-        backends::{
-            cpu,
-            gpu::{self, offscreen::context::GpuCtx},
-        },
-        strategy::{convolve, prepare},
-        Backend,
-    },
+    batch::{self, Output},
     prelude::*,
 };
 use tracing::info;
@@ -15,34 +8,43 @@ use tracing::info;
 fn main() -> Result<()> {
     let args = Cli::parse();
 
-    tracing_subscriber::fmt().init();
+    // Logs go to stderr, since stdout may be the output image.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
     info!(?args, "CLI");
 
-    // For brevity
-    let (input, output, kernel) = (&args.input, &args.output, args.kernel);
-
-    let image = prepare(input)?;
-
-    match args.backend {
-        Backend::SingleNestedLoops => {
-            let backend = cpu::single::NestedLoops::from((image, kernel));
-            convolve(backend, output)?;
-        }
-        Backend::SingleNestedIterators => {
-            let backend = cpu::single::NestedIterators::from((image, kernel));
-            convolve(backend, output)?;
-        }
-        Backend::MultiRayon => {
-            let backend = cpu::multi::NestedIterators::from((image, kernel));
-            convolve(backend, output)?;
-        }
-        Backend::GpuOffscreen => {
-            let ctx = GpuCtx::new(image)?;
-            let backend = gpu::offscreen::Offscreen::new(ctx, kernel)?;
-            convolve(backend, output)?;
-        }
+    let kernels = args.load_kernels()?;
+    let options = args.options();
+
+    let inputs = batch::expand_inputs(&args.input)?;
+    if inputs.is_empty() {
+        return Err(Error::InvalidInput("no images found".into()));
+    }
+
+    let (output, kernel_name) = (args.output(), args.kernel_name());
+    if inputs.len() > 1 && matches!(output, Output::File(_)) {
+        return Err(Error::InvalidInput(format!(
+            "{} images cannot be written to a single output",
+            inputs.len()
+        )));
     }
 
-    Ok(())
+    if batch::is_stdio(&args.output) && args.format.is_none() {
+        return Err(Error::InvalidInput(
+            "--format is needed when writing to stdout".into(),
+        ));
+    }
+
+    let jobs: Vec<_> = inputs
+        .into_iter()
+        .map(|input| {
+            let output = output.path(&input, &kernel_name);
+            (input, output)
+        })
+        .collect();
+    batch::check_jobs(&jobs)?;
+
+    batch::run(jobs, &kernels, args.backend, options, args.format).into_result()
 }
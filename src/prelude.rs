@@ -1,6 +1,11 @@
 pub use crate::{
     cli::Cli,
-    convolution::strategy::ConvolveStrategy,
+    convolution::{
+        image_ext::ConvolveImage,
+        options::{AlphaMode, BorderMode, ColorSpace, Options},
+        strategy::ConvolveStrategy,
+        Backend,
+    },
     error::{Error, Result},
     kernel::Kernel,
 };
@@ -0,0 +1,126 @@
+mod common;
+
+use common::run;
+use image::{ColorType, DynamicImage, Rgba, Rgba32FImage, RgbaImage};
+use image_convolve::{
+    convolution::{backends::cpu, strategy},
+    prelude::*,
+};
+
+/// An opaque square in the middle, surrounded by transparent pixels.
+fn sprite() -> Rgba32FImage {
+    Rgba32FImage::from_fn(16, 16, |x, y| {
+        if (4..12).contains(&x) && (4..12).contains(&y) {
+            Rgba([1., 0.5, 0.25, 1.])
+        } else {
+            Rgba([0., 0., 0., 0.])
+        }
+    })
+}
+
+#[test]
+fn keeps_color_type() {
+    let sprite = DynamicImage::ImageRgba32F(sprite());
+
+    for color_type in [
+        ColorType::L8,
+        ColorType::La8,
+        ColorType::Rgb8,
+        ColorType::Rgba8,
+        ColorType::L16,
+        ColorType::La16,
+        ColorType::Rgb16,
+        ColorType::Rgba16,
+        ColorType::Rgb32F,
+        ColorType::Rgba32F,
+    ] {
+        let input = match color_type {
+            ColorType::L8 => sprite.to_luma8().into(),
+            ColorType::La8 => sprite.to_luma_alpha8().into(),
+            ColorType::Rgb8 => sprite.to_rgb8().into(),
+            ColorType::Rgba8 => sprite.to_rgba8().into(),
+            ColorType::L16 => sprite.to_luma16().into(),
+            ColorType::La16 => sprite.to_luma_alpha16().into(),
+            ColorType::Rgb16 => sprite.to_rgb16().into(),
+            ColorType::Rgba16 => sprite.to_rgba16().into(),
+            ColorType::Rgb32F => sprite.to_rgb32f().into(),
+            _ => sprite.clone(),
+        };
+
+        let output = run(cpu::single::NestedIterators::from((
+            input.clone(),
+            Kernel::BoxBlur,
+        )));
+        assert_eq!(output.color(), color_type);
+
+        #[cfg(feature = "rayon")]
+        {
+            let output = run(cpu::multi::NestedIterators::from((input, Kernel::BoxBlur)));
+            assert_eq!(output.color(), color_type);
+        }
+    }
+}
+
+#[test]
+fn passes_alpha_through() {
+    let input = sprite();
+    let output = run(cpu::single::NestedLoops::from((
+        DynamicImage::ImageRgba32F(input.clone()),
+        Kernel::GaussianBlur,
+    )));
+
+    for (x, y, pixel) in output.to_rgba32f().enumerate_pixels() {
+        assert_eq!(pixel[3], input.get_pixel(x, y)[3], "({x}, {y})");
+    }
+}
+
+#[test]
+fn convolves_alpha() {
+    let options = Options {
+        alpha: AlphaMode::Convolve,
+        ..Options::default()
+    };
+    let output = run(cpu::single::NestedLoops::from((
+        DynamicImage::ImageRgba32F(sprite()),
+        Kernel::BoxBlur,
+        options,
+    )))
+    .to_rgba32f();
+
+    // Corner of the square: 4 of 9 neighbours are opaque.
+    assert!((output.get_pixel(4, 4)[3] - 4. / 9.).abs() < 1e-6);
+    // Just outside the edge: 3 of 9 neighbours are opaque.
+    assert!((output.get_pixel(3, 8)[3] - 3. / 9.).abs() < 1e-6);
+    // Far from the square everything stays transparent.
+    assert_eq!(output.get_pixel(0, 0)[3], 0.);
+}
+
+#[test]
+fn keeps_16_bit_rgba_png() {
+    let path = std::env::temp_dir().join("image-convolve-keeps-16-bit-rgba.png");
+
+    let input = DynamicImage::ImageRgba32F(sprite()).to_rgba16();
+    let backend = cpu::single::NestedIterators::from((input.clone().into(), Kernel::Sharpen));
+    strategy::convolve(backend, &path).unwrap();
+
+    let output = strategy::prepare(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.color(), ColorType::Rgba16);
+    // An opaque pixel in the flat interior of the square is unchanged by sharpening.
+    assert_eq!(output.to_rgba16().get_pixel(8, 8), input.get_pixel(8, 8));
+}
+
+#[test]
+fn drops_alpha_for_formats_without_it() {
+    let path = std::env::temp_dir().join("image-convolve-drops-alpha.jpg");
+
+    let input: RgbaImage = DynamicImage::ImageRgba32F(sprite()).to_rgba8();
+    let backend = cpu::single::NestedIterators::from((input.into(), Kernel::Identity));
+    strategy::convolve(backend, &path).unwrap();
+
+    let output = strategy::prepare(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.color(), ColorType::Rgb8);
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use common::test_image;
+use image_convolve::{
+    batch::{self, Output},
+    prelude::*,
+};
+
+/// A fresh directory with two images and a file which is not a valid image.
+fn images(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("image-convolve-batch-{name}"));
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+
+    for name in ["a.png", "b.png"] {
+        test_image(8, 8)
+            .to_rgb8()
+            .save(directory.join(name))
+            .unwrap();
+    }
+    std::fs::write(directory.join("broken.png"), "not a png").unwrap();
+    std::fs::write(directory.join("notes.txt"), "not an image").unwrap();
+
+    directory
+}
+
+fn cli(args: &[&str]) -> Cli {
+    let required = ["image-convolve", "-k=sharpen", "-b=auto"];
+    Cli::try_parse_from(required.iter().chain(args)).unwrap()
+}
+
+#[test]
+fn expands_directories_and_patterns() {
+    let directory = images("expand");
+
+    let from_directory = batch::expand_inputs(std::slice::from_ref(&directory)).unwrap();
+    let from_pattern = batch::expand_inputs(&[directory.join("*.png")]).unwrap();
+
+    let expected: Vec<PathBuf> = ["a.png", "b.png", "broken.png"]
+        .iter()
+        .map(|name| directory.join(name))
+        .collect();
+    assert_eq!(from_directory, expected);
+    assert_eq!(from_pattern, expected);
+}
+
+#[test]
+fn names_outputs() {
+    let input = Path::new("in/photo.jpg");
+
+    let template = Output::Template("out/{stem}-{kernel}.{ext}".into());
+    assert_eq!(
+        template.path(input, "sharpen"),
+        Path::new("out/photo-sharpen.jpg")
+    );
+
+    let directory = Output::Directory("out".into());
+    assert_eq!(directory.path(input, "sharpen"), Path::new("out/photo.jpg"));
+}
+
+#[test]
+fn cli_chooses_output() {
+    assert_eq!(
+        cli(&["-i=a.png", "-o=b.png"]).output(),
+        Output::File("b.png".into())
+    );
+    assert_eq!(
+        cli(&["-i", "a.png", "b.png", "-o=out"]).output(),
+        Output::Directory("out".into())
+    );
+    assert_eq!(
+        cli(&["-i=*.png", "-o=out/{stem}.{ext}"]).output(),
+        Output::Template("out/{stem}.{ext}".into())
+    );
+
+    let args = cli(&["-i=a.png", "-o=b.png", "-k=gaussian-blur"]);
+    assert_eq!(args.kernel_name(), "sharpen-gaussian-blur");
+}
+
+#[test]
+fn collects_errors() {
+    let directory = images("errors");
+    let output = Output::Template(
+        directory
+            .join("out/{stem}-{kernel}.{ext}")
+            .to_string_lossy()
+            .into(),
+    );
+
+    let jobs = batch::expand_inputs(std::slice::from_ref(&directory))
+        .unwrap()
+        .into_iter()
+        .map(|input| {
+            let output = output.path(&input, "sharpen");
+            (input, output)
+        });
+    let summary = batch::run(
+        jobs,
+        &[Kernel::Sharpen.into()],
+        Backend::Auto,
+        Options::default(),
+        None,
+    );
+
+    assert_eq!(
+        summary.succeeded,
+        [
+            directory.join("out/a-sharpen.png"),
+            directory.join("out/b-sharpen.png")
+        ]
+    );
+    assert!(summary.succeeded.iter().all(|path| path.is_file()));
+
+    assert_eq!(summary.failed.len(), 1);
+    assert_eq!(summary.failed[0].0, directory.join("broken.png"));
+
+    let error = summary.into_result().unwrap_err();
+    assert!(error.to_string().contains("broken.png"), "{error}");
+    match error {
+        Error::Batch { failures, total } => {
+            assert_eq!(total, 3);
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].0, directory.join("broken.png"));
+        }
+        e => panic!("unexpected error: {e}"),
+    }
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn rejects_conflicting_outputs() {
+    let directory = images("conflicts");
+    let inputs = batch::expand_inputs(std::slice::from_ref(&directory)).unwrap();
+    let check = |output: Output| {
+        let jobs: Vec<_> = inputs
+            .iter()
+            .map(|input| (input.clone(), output.path(input, "sharpen")))
+            .collect();
+        batch::check_jobs(&jobs)
+    };
+
+    // Writing into the input directory overwrites the inputs,
+    // even when it is spelled differently.
+    assert!(matches!(
+        check(Output::Directory(directory.clone())),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        check(Output::Directory(directory.join("."))),
+        Err(Error::InvalidInput(_))
+    ));
+
+    // Without `{stem}` every input is written to the same file.
+    let template = directory.join("out/{kernel}.{ext}");
+    assert!(matches!(
+        check(Output::Template(template.to_string_lossy().into())),
+        Err(Error::InvalidInput(_))
+    ));
+
+    let template = directory.join("{stem}-{kernel}.{ext}");
+    check(Output::Template(template.to_string_lossy().into())).unwrap();
+    check(Output::Directory(directory.join("out"))).unwrap();
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
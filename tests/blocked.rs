@@ -0,0 +1,147 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{run, test_image};
+use image::DynamicImage;
+use image_convolve::{convolution::backends::cpu, kernel::KernelImpl, prelude::*};
+
+/// Block sizes of a single pixel, not dividing the image, and larger than the image.
+const BLOCK_SIZES: [(u32, u32); 4] = [(1, 1), (5, 3), (8, 8), (100, 100)];
+
+/// Image sizes which are not multiples of the block sizes.
+const IMAGE_SIZES: [(u32, u32); 2] = [(23, 19), (17, 30)];
+
+/// The shared kernels, along with one whose halo is larger than the smaller blocks,
+/// and one which is wider than the images.
+fn kernels() -> Vec<KernelImpl> {
+    let mut kernels = common::kernels();
+    kernels.extend([
+        KernelImpl::gaussian(3., None).unwrap(),
+        KernelImpl::new(25, 3, (0..75).map(|i| (i % 5) as f32).collect(), 1. / 150.).unwrap(),
+    ]);
+
+    kernels
+}
+
+fn nested_loops(image: &DynamicImage, kernel: &KernelImpl, options: Options) -> Vec<f32> {
+    run(cpu::single::NestedLoops::from((
+        image.clone(),
+        kernel.clone(),
+        options,
+    )))
+    .to_rgba32f()
+    .into_raw()
+}
+
+#[test]
+fn matches_nested_loops_exactly() {
+    for (width, height) in IMAGE_SIZES {
+        let image = test_image(width, height);
+
+        for kernel in kernels() {
+            for &border in BorderMode::value_variants() {
+                let options = Options {
+                    border,
+                    ..Options::default()
+                };
+                let expected = nested_loops(&image, &kernel, options);
+
+                let output = run(cpu::blocked::Single::from((
+                    image.clone(),
+                    kernel.clone(),
+                    options,
+                )));
+                assert_eq!(
+                    output.to_rgba32f().into_raw(),
+                    expected,
+                    "{width}x{height} image, {border:?}"
+                );
+
+                for (block_width, block_height) in BLOCK_SIZES {
+                    let output =
+                        run(
+                            cpu::blocked::Single::from((image.clone(), kernel.clone(), options))
+                                .with_block_size(block_width, block_height),
+                        );
+
+                    assert_eq!(
+                        output.to_rgba32f().into_raw(),
+                        expected,
+                        "{width}x{height} image, {block_width}x{block_height} blocks, {border:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn multi_matches_nested_loops_exactly() {
+    for (width, height) in IMAGE_SIZES {
+        let image = test_image(width, height);
+
+        for kernel in kernels() {
+            for &border in BorderMode::value_variants() {
+                let options = Options {
+                    border,
+                    ..Options::default()
+                };
+                let expected = nested_loops(&image, &kernel, options);
+
+                let output = run(cpu::blocked::Multi::from((
+                    image.clone(),
+                    kernel.clone(),
+                    options,
+                )));
+                assert_eq!(
+                    output.to_rgba32f().into_raw(),
+                    expected,
+                    "{width}x{height} image, {border:?}"
+                );
+
+                for (block_width, block_height) in BLOCK_SIZES {
+                    let output =
+                        run(
+                            cpu::blocked::Multi::from((image.clone(), kernel.clone(), options))
+                                .with_block_size(block_width, block_height),
+                        );
+
+                    assert_eq!(
+                        output.to_rgba32f().into_raw(),
+                        expected,
+                        "{width}x{height} image, {block_width}x{block_height} blocks, {border:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// The default blocks are several times smaller than this image,
+/// which is not a multiple of them either.
+#[test]
+fn default_blocks_match_nested_loops_exactly() {
+    let image = test_image(251, 199);
+
+    for kernel in common::kernels() {
+        let expected = nested_loops(&image, &kernel, Options::default());
+        let output = run(cpu::blocked::Single::from((image.clone(), kernel)));
+
+        assert_eq!(output.to_rgba32f().into_raw(), expected);
+    }
+}
+
+#[test]
+fn zero_block_size_is_one_pixel() {
+    let image = test_image(9, 7);
+    let kernel = KernelImpl::from(Kernel::Sharpen);
+
+    let output =
+        run(cpu::blocked::Single::from((image.clone(), kernel.clone())).with_block_size(0, 0));
+
+    assert_eq!(
+        output.to_rgba32f().into_raw(),
+        nested_loops(&image, &kernel, Options::default())
+    );
+}
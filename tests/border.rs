@@ -0,0 +1,119 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{assert_close, test_image};
+use image::{DynamicImage, Luma};
+#[cfg(feature = "gpu")]
+use image_convolve::convolution::backends::gpu::offscreen::context::GpuDevice;
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// The row `abcd`, as in the documentation of [`BorderMode`].
+fn abcd() -> DynamicImage {
+    image::ImageBuffer::from_fn(4, 1, |x, _| Luma([[0.1f32, 0.2, 0.3, 0.4][x as usize]])).into()
+}
+
+/// A 5x1 kernel reading the pixel `offset` to the right of the center, in `-2..=2`.
+fn shift(offset: i32) -> KernelImpl {
+    let mut weights = vec![0.; 5];
+    weights[(offset + 2) as usize] = 1.;
+
+    KernelImpl::new(5, 1, weights, 1.).unwrap()
+}
+
+fn srgb(border: BorderMode) -> Options {
+    Options {
+        border,
+        border_color: [0.9, 0.9, 0.9, 1.],
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    }
+}
+
+/// The row extended by two pixels on each side, read back by shifting it two pixels
+/// either way, checked against the documented examples rather than another backend.
+#[test]
+fn extends_rows_as_documented() {
+    let (a, b, c, d, x) = (0.1, 0.2, 0.3, 0.4, 0.9);
+    let cases = [
+        (BorderMode::Clamp, [a, a, a, b, c, d, d, d]),
+        (BorderMode::Mirror, [b, a, a, b, c, d, d, c]),
+        (BorderMode::Wrap, [c, d, a, b, c, d, a, b]),
+        (BorderMode::Constant, [x, x, a, b, c, d, x, x]),
+    ];
+
+    for backend in [
+        Backend::SingleNestedLoops,
+        Backend::SingleNestedIterators,
+        Backend::SingleSimd,
+        Backend::Reference,
+    ] {
+        for (border, extended) in cases {
+            // Shifting by -2 shows the left padding, and by 2 the right padding.
+            let left = abcd().convolve(shift(-2), backend, srgb(border)).unwrap();
+            let right = abcd().convolve(shift(2), backend, srgb(border)).unwrap();
+
+            let left = left.to_luma32f().into_raw();
+            let right = right.to_luma32f().into_raw();
+            assert_eq!(&left[..2], &extended[..2], "{backend:?} {border:?}");
+            assert_eq!(&right[2..], &extended[6..], "{backend:?} {border:?}");
+        }
+    }
+}
+
+#[test]
+fn crop_only_keeps_pixels_the_kernel_fits_over() {
+    // Reads the pixel left of the center.
+    let kernel = KernelImpl::new(3, 1, vec![1., 0., 0.], 1.).unwrap();
+    let output = abcd()
+        .convolve(kernel, Backend::SingleNestedLoops, srgb(BorderMode::Crop))
+        .unwrap();
+
+    assert_eq!(output.to_luma32f().into_raw(), vec![0.1, 0.2]);
+}
+
+/// Every backend agrees with the nested loops for each border mode,
+/// with an asymmetric kernel such that mirrored or shifted reads show up.
+/// The GPU backend is skipped if there is no adapter.
+#[test]
+fn backends_agree_for_each_mode() {
+    #[cfg(feature = "gpu")]
+    let gpu_available = !matches!(GpuDevice::shared(), Err(Error::AdapterUnavailable));
+
+    let input = test_image(13, 9);
+    let kernel =
+        KernelImpl::new(5, 3, (0..15).map(|i| (i % 4) as f32).collect(), 1. / 22.).unwrap();
+
+    for &border in BorderMode::value_variants() {
+        let options = Options {
+            border,
+            border_color: [0.25, 0.5, 0.75, 1.],
+            ..Options::default()
+        };
+        let expected = input
+            .clone()
+            .convolve(kernel.clone(), Backend::SingleNestedLoops, options)
+            .unwrap();
+
+        for &backend in Backend::value_variants() {
+            let tolerance = match backend {
+                // Only supports 8 bit images, see `tests/fixed_point.rs`.
+                Backend::FixedPoint => continue,
+                #[cfg(feature = "gpu")]
+                Backend::GpuOffscreen if !gpu_available => continue,
+                #[cfg(feature = "gpu")]
+                Backend::GpuOffscreen => 1e-4,
+                Backend::Fft => 1e-4,
+                _ => 1e-5,
+            };
+
+            let output = match input.clone().convolve(kernel.clone(), backend, options) {
+                Ok(output) => output,
+                // E.g. the separable backends, the kernel is not separable.
+                Err(Error::UnsupportedKernel(_)) => continue,
+                Err(e) => panic!("{backend:?} with {border:?}: {e}"),
+            };
+
+            assert_close(&output, &expected, tolerance);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "rayon")]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::{run, test_image};
+use image::DynamicImage;
+use image_convolve::{
+    convolution::backends::cpu::{multi::Chunked, single::NestedLoops},
+    prelude::*,
+};
+
+fn expected(input: &DynamicImage, options: Options) -> Vec<f32> {
+    run(NestedLoops::from((input.clone(), Kernel::Sharpen, options)))
+        .to_rgba32f()
+        .into_raw()
+}
+
+#[test]
+fn matches_nested_loops_for_any_band_height() {
+    let input = test_image(19, 23);
+
+    for border in [BorderMode::Clamp, BorderMode::Crop] {
+        let options = Options {
+            border,
+            ..Options::default()
+        };
+        let expected = expected(&input, options);
+
+        let default = run(Chunked::from((input.clone(), Kernel::Sharpen, options)));
+        assert_eq!(default.to_rgba32f().into_raw(), expected);
+
+        // Including bands which do not divide the height, and a single band.
+        for band_height in [0, 1, 2, 5, 23, 100] {
+            let output = run(Chunked::from((input.clone(), Kernel::Sharpen, options))
+                .with_band_height(band_height));
+
+            assert_eq!(
+                output.to_rgba32f().into_raw(),
+                expected,
+                "band height {band_height}, {border:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn runs_on_custom_thread_pools() {
+    let input = test_image(16, 16);
+    let expected = expected(&input, Options::default());
+
+    let output = run(Chunked::from((input.clone(), Kernel::Sharpen))
+        .with_threads(2)
+        .unwrap());
+    assert_eq!(output.to_rgba32f().into_raw(), expected);
+
+    let thread_pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap(),
+    );
+    let output = run(Chunked::from((input, Kernel::Sharpen))
+        .with_band_height(3)
+        .with_thread_pool(thread_pool));
+    assert_eq!(output.to_rgba32f().into_raw(), expected);
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use common::{assert_close, run, test_image};
+use image::{DynamicImage, Luma, Rgb32FImage};
+use image_convolve::{convolution::backends::cpu, kernel::KernelImpl, prelude::*};
+
+/// A checkerboard of black and white pixels.
+fn checkerboard() -> DynamicImage {
+    DynamicImage::ImageLuma8(image::GrayImage::from_fn(8, 8, |x, y| {
+        Luma([if (x + y).is_multiple_of(2) { 255 } else { 0 }])
+    }))
+    .to_rgb32f()
+    .into()
+}
+
+fn blur(input: DynamicImage, color_space: ColorSpace) -> Rgb32FImage {
+    let kernel = KernelImpl::new(3, 1, vec![1., 2., 1.], 0.25).unwrap();
+    let options = Options {
+        border: BorderMode::Wrap,
+        color_space,
+        ..Options::default()
+    };
+
+    run(cpu::single::NestedLoops::from((input, kernel, options))).to_rgb32f()
+}
+
+#[test]
+fn srgb_averages_encoded_values() {
+    let output = blur(checkerboard(), ColorSpace::Srgb);
+
+    for pixel in output.pixels() {
+        assert!((pixel.0[0] - 0.5).abs() < 1e-6, "{pixel:?}");
+    }
+}
+
+#[test]
+fn linear_averages_light() {
+    let output = blur(checkerboard(), ColorSpace::Linear);
+
+    // Half the light of white is encoded as roughly 0.735 in sRGB.
+    for pixel in output.pixels() {
+        assert!((pixel.0[0] - 0.735_357).abs() < 1e-4, "{pixel:?}");
+    }
+}
+
+#[test]
+fn identity_round_trips() {
+    let input = test_image(16, 16);
+    let kernel = KernelImpl::new(1, 1, vec![1.], 1.).unwrap();
+    let output = run(cpu::single::NestedLoops::from((
+        input.clone(),
+        kernel,
+        Options::default(),
+    )));
+
+    assert_close(&output, &input, 1e-5);
+}
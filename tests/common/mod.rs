@@ -0,0 +1,80 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use image::{DynamicImage, Rgb, Rgb32FImage};
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// A deterministic image with some structure in every channel,
+/// such that errors in indexing or border handling show up.
+///
+/// The image is in a float format, such that outputs are not quantized.
+pub fn test_image(width: u32, height: u32) -> DynamicImage {
+    Rgb32FImage::from_fn(width, height, |x, y| {
+        Rgb([
+            x as f32 / width as f32,
+            y as f32 / height as f32,
+            ((x * 7 + y * 13) % 256) as f32 / 255.,
+        ])
+    })
+    .into()
+}
+
+/// Kernels for checking backends which must match the nested loops exactly,
+/// such that mistakes in the order of weights or the kernel radius show up.
+pub fn kernels() -> Vec<KernelImpl> {
+    vec![
+        Kernel::EdgeDetection2.into(),
+        Kernel::Sharpen.into(),
+        KernelImpl::gaussian(2., None).unwrap(),
+        // Non-square and asymmetric, with a bias
+        KernelImpl::new(
+            5,
+            3,
+            (0..15).map(|i| (i * 7 % 11) as f32 - 5.).collect(),
+            0.1,
+        )
+        .unwrap()
+        .with_bias(0.25),
+    ]
+}
+
+/// Run a backend to completion.
+pub fn run<S: ConvolveStrategy>(mut backend: S) -> DynamicImage {
+    backend.convolve().unwrap();
+    backend.finish().unwrap()
+}
+
+/// Assert two images have the same dimensions and all channels are within the tolerance.
+pub fn assert_close(actual: &DynamicImage, expected: &DynamicImage, tolerance: f32) {
+    let (actual, expected) = (actual.to_rgba32f(), expected.to_rgba32f());
+    assert_eq!(actual.dimensions(), expected.dimensions());
+
+    for (x, y, actual_pixel) in actual.enumerate_pixels() {
+        let expected_pixel = expected.get_pixel(x, y);
+
+        for (a, e) in actual_pixel.0.iter().zip(expected_pixel.0) {
+            assert!(
+                (a - e).abs() <= tolerance,
+                "pixel ({x}, {y}): {actual_pixel:?} vs expected {expected_pixel:?}"
+            );
+        }
+    }
+}
+
+/// Like [`assert_close`], but channels larger than one may differ relative to their size,
+/// since e.g. dividing by a small alpha or sRGB encoding magnifies rounding errors.
+pub fn assert_close_relative(actual: &DynamicImage, expected: &DynamicImage, tolerance: f32) {
+    let (actual, expected) = (actual.to_rgba32f(), expected.to_rgba32f());
+    assert_eq!(actual.dimensions(), expected.dimensions());
+
+    for (x, y, actual_pixel) in actual.enumerate_pixels() {
+        let expected_pixel = expected.get_pixel(x, y);
+
+        for (a, e) in actual_pixel.0.iter().zip(expected_pixel.0) {
+            assert!(
+                (a - e).abs() <= tolerance * e.abs().max(1.),
+                "pixel ({x}, {y}): {actual_pixel:?} vs expected {expected_pixel:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use common::{assert_close, test_image};
+use image::{DynamicImage, GenericImageView};
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// Pairs of kernels to compose, including non-square, asymmetric and biased ones.
+fn pairs() -> Vec<(KernelImpl, KernelImpl)> {
+    vec![
+        (Kernel::GaussianBlur.into(), Kernel::EdgeDetection1.into()),
+        (
+            KernelImpl::gaussian(1.5, None).unwrap(),
+            Kernel::Sharpen.into(),
+        ),
+        (
+            KernelImpl::new(3, 1, vec![1., 2., 4.], 1. / 7.)
+                .unwrap()
+                .with_bias(0.1),
+            KernelImpl::new(1, 5, vec![-1., 0., 3., 1., 0.], 0.5)
+                .unwrap()
+                .with_bias(-0.2),
+        ),
+    ]
+}
+
+fn two_passes(
+    input: DynamicImage,
+    first: &KernelImpl,
+    second: &KernelImpl,
+    options: Options,
+) -> DynamicImage {
+    input
+        .convolve_pipeline([first.clone(), second.clone()], Backend::Auto, options)
+        .unwrap()
+}
+
+#[test]
+fn one_pass_equals_two_passes_when_cropping() {
+    let options = Options {
+        border: BorderMode::Crop,
+        ..Options::default()
+    };
+
+    for (first, second) in pairs() {
+        let input = test_image(32, 24);
+        let expected = two_passes(input.clone(), &first, &second, options);
+        let output = input
+            .convolve(first.compose(&second), Backend::Auto, options)
+            .unwrap();
+
+        assert_close(&output, &expected, 1e-4);
+    }
+}
+
+#[test]
+fn one_pass_equals_two_passes_in_interior() {
+    for (first, second) in pairs() {
+        let input = test_image(32, 24);
+        let expected = two_passes(input.clone(), &first, &second, Options::default());
+
+        let composed = first.compose(&second);
+        let output = input
+            .convolve(composed.clone(), Backend::Auto, Options::default())
+            .unwrap();
+
+        // Only pixels where the composed kernel does not reach past the edges.
+        let (radius_x, radius_y) = composed.radius();
+        let (width, height) = (32 - 2 * radius_x, 24 - 2 * radius_y);
+        let interior = |image: &DynamicImage| -> DynamicImage {
+            image
+                .view(radius_x, radius_y, width, height)
+                .to_image()
+                .into()
+        };
+
+        assert_close(&interior(&output), &interior(&expected), 1e-4);
+    }
+}
+
+#[test]
+fn composed_size_and_normalization() {
+    let composed = KernelImpl::from(Kernel::GaussianBlur).compose(&Kernel::BoxBlur.into());
+
+    assert_eq!((composed.width(), composed.height()), (5, 5));
+
+    // Both kernels preserve brightness, so the composition does too.
+    let sum: f32 = composed.weights().iter().sum();
+    assert!((sum * composed.normalization() - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn composition_keeps_orientation() {
+    // Reads the pixel to the left, so twice reads two pixels to the left.
+    let shift = KernelImpl::new(3, 1, vec![1., 0., 0.], 1.).unwrap();
+    let composed = shift.compose(&shift);
+
+    assert_eq!(composed.weights(), &[1., 0., 0., 0., 0.]);
+}
+
+#[test]
+fn cli_merges_kernels() {
+    use clap::Parser;
+
+    let args = Cli::try_parse_from([
+        "image-convolve",
+        "-i=in.png",
+        "-o=out.png",
+        "-b=auto",
+        "--kernel=gaussian-blur,edge-detection1",
+        "--merge-kernels",
+    ])
+    .unwrap();
+
+    let expected = KernelImpl::from(Kernel::GaussianBlur).compose(&Kernel::EdgeDetection1.into());
+    assert_eq!(args.load_kernels().unwrap(), vec![expected]);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{assert_close, run, test_image};
+use image_convolve::{
+    convolution::{
+        backends::cpu::{self, fft::is_preferable},
+        Backend,
+    },
+    kernel::KernelImpl,
+    prelude::*,
+};
+
+fn kernels() -> Vec<KernelImpl> {
+    vec![
+        Kernel::Identity.into(),
+        Kernel::EdgeDetection2.into(),
+        Kernel::Sharpen.into(),
+        KernelImpl::gaussian(2., None).unwrap(),
+        // Asymmetric and non-square, which catches a missing kernel flip
+        KernelImpl::new(
+            7,
+            5,
+            (0..35).map(|i| ((i * 17) % 11) as f32 - 5.).collect(),
+            0.05,
+        )
+        .unwrap()
+        .with_bias(0.1),
+    ]
+}
+
+#[test]
+fn matches_direct_convolution() {
+    let image = test_image(41, 29);
+
+    for kernel in kernels() {
+        for &border in BorderMode::value_variants() {
+            let options = Options {
+                border,
+                border_color: [0.9, 0.5, 0.1, 1.0],
+                ..Options::default()
+            };
+
+            let expected = run(cpu::single::NestedLoops::from((
+                image.clone(),
+                kernel.clone(),
+                options,
+            )));
+            let actual = run(cpu::fft::Fft::from((
+                image.clone(),
+                kernel.clone(),
+                options,
+            )));
+
+            assert_close(&actual, &expected, 1e-4);
+        }
+    }
+}
+
+#[test]
+fn prefers_fft_for_large_kernels() {
+    let dimensions = (1920, 1080);
+    let sharpen = KernelImpl::from(Kernel::Sharpen);
+    let large = KernelImpl::new(31, 31, vec![1.; 31 * 31], 1.).unwrap();
+
+    assert!(!is_preferable(&sharpen, dimensions));
+    assert!(is_preferable(&large, dimensions));
+
+    // Small kernels are convolved directly, and separable kernels are cheaper still
+    // with two 1D passes, multi threaded if possible.
+    #[cfg(feature = "rayon")]
+    {
+        assert_eq!(Backend::choose(&sharpen, dimensions), Backend::MultiRayon);
+        assert_eq!(Backend::choose(&large, dimensions), Backend::MultiSeparable);
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        assert_eq!(Backend::choose(&sharpen, dimensions), Backend::SingleSimd);
+        assert_eq!(
+            Backend::choose(&large, dimensions),
+            Backend::SingleSeparable
+        );
+    }
+
+    let large_non_separable = KernelImpl::new(
+        31,
+        31,
+        (0..31 * 31)
+            .map(|i| (i % 7) as f32 * (i % 3) as f32)
+            .collect(),
+        1.,
+    )
+    .unwrap();
+    assert_eq!(
+        Backend::choose(&large_non_separable, dimensions),
+        Backend::Fft
+    );
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use clap::ValueEnum;
+use common::test_image;
+use image::{DynamicImage, Rgba, RgbaImage};
+use image_convolve::{batch, kernel::KernelImpl, prelude::*};
+
+fn srgb(border: BorderMode, alpha: AlphaMode) -> Options {
+    Options {
+        border,
+        alpha,
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    }
+}
+
+/// 8 bit images with and without varying transparency.
+fn images() -> Vec<DynamicImage> {
+    let colors = test_image(23, 17).to_rgba8();
+    let translucent = RgbaImage::from_fn(23, 17, |x, y| {
+        let [red, green, blue, _] = colors.get_pixel(x, y).0;
+        Rgba([red, green, blue, ((x * 37 + y * 11) % 256) as u8])
+    });
+
+    vec![
+        test_image(23, 17).to_rgb8().into(),
+        translucent.into(),
+        test_image(23, 17).to_luma8().into(),
+    ]
+}
+
+/// Assert the images have the same color type and dimensions, and differ by at most one.
+fn assert_within_one(actual: &DynamicImage, expected: &DynamicImage, context: &str) {
+    assert_eq!(actual.color(), expected.color(), "{context}");
+    assert_eq!(
+        actual.as_bytes().len(),
+        expected.as_bytes().len(),
+        "{context}"
+    );
+
+    for (a, e) in actual.as_bytes().iter().zip(expected.as_bytes()) {
+        assert!(a.abs_diff(*e) <= 1, "{context}: {a} vs expected {e}");
+    }
+}
+
+#[test]
+fn within_one_of_float_path() {
+    for input in images() {
+        for &kernel in Kernel::value_variants() {
+            for &border in BorderMode::value_variants() {
+                for alpha in [AlphaMode::PassThrough, AlphaMode::Convolve] {
+                    let options = srgb(border, alpha);
+                    let expected = input
+                        .clone()
+                        .convolve(kernel, Backend::SingleNestedLoops, options)
+                        .unwrap();
+                    let output = input
+                        .clone()
+                        .convolve(kernel, Backend::FixedPoint, options)
+                        .unwrap();
+
+                    let context = format!("{:?} {kernel:?} {border:?} {alpha:?}", input.color());
+                    assert_within_one(&output, &expected, &context);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn supports_integer_kernels_with_bias() {
+    let kernel = KernelImpl::new(3, 5, (0..15).map(|i| (i % 4) as f32 - 1.).collect(), 0.125)
+        .unwrap()
+        .with_bias(0.5);
+    let input: DynamicImage = test_image(12, 9).to_rgb8().into();
+    let options = srgb(BorderMode::Mirror, AlphaMode::PassThrough);
+
+    let expected = input
+        .clone()
+        .convolve(kernel.clone(), Backend::SingleNestedLoops, options)
+        .unwrap();
+    let output = input
+        .convolve(kernel, Backend::FixedPoint, options)
+        .unwrap();
+
+    assert_within_one(&output, &expected, "bias");
+}
+
+#[test]
+fn rejects_what_needs_more_precision() {
+    let input: DynamicImage = test_image(8, 8).to_rgb8().into();
+    let options = srgb(BorderMode::Clamp, AlphaMode::PassThrough);
+
+    let gaussian = KernelImpl::gaussian(1., None).unwrap();
+    assert!(matches!(
+        input
+            .clone()
+            .convolve(gaussian, Backend::FixedPoint, options),
+        Err(Error::UnsupportedKernel(_))
+    ));
+
+    let thirds = KernelImpl::new(1, 1, vec![1.], 2. / 3.).unwrap();
+    assert!(matches!(
+        input.clone().convolve(thirds, Backend::FixedPoint, options),
+        Err(Error::UnsupportedKernel(_))
+    ));
+
+    for options in [
+        Options::default(),
+        Options {
+            premultiply: true,
+            ..options
+        },
+    ] {
+        assert!(matches!(
+            input
+                .clone()
+                .convolve(Kernel::BoxBlur, Backend::FixedPoint, options),
+            Err(Error::UnsupportedBackend(_))
+        ));
+    }
+
+    assert!(matches!(
+        test_image(8, 8).convolve(Kernel::BoxBlur, Backend::FixedPoint, options),
+        Err(Error::UnsupportedBackend(_))
+    ));
+}
+
+/// The CLI convolves via pipelines and batches, which must hand the backend 8 bit images.
+#[test]
+fn runs_in_pipelines_and_batches() {
+    let input: DynamicImage = test_image(12, 9).to_rgb8().into();
+    let options = srgb(BorderMode::Clamp, AlphaMode::PassThrough);
+
+    let sharpened = input
+        .clone()
+        .convolve(Kernel::Sharpen, Backend::FixedPoint, options)
+        .unwrap();
+    let blurred = sharpened
+        .clone()
+        .convolve(Kernel::BoxBlur, Backend::FixedPoint, options)
+        .unwrap();
+
+    let single = Backend::FixedPoint
+        .convolve_pipeline(input.clone(), [Kernel::Sharpen], options)
+        .unwrap();
+    assert_eq!(single, sharpened);
+
+    let kernels = [Kernel::Sharpen, Kernel::BoxBlur];
+    let stages = Backend::FixedPoint
+        .convolve_pipeline(input.clone(), kernels, options)
+        .unwrap();
+    assert_eq!(stages, blurred);
+
+    let directory = std::env::temp_dir().join("image-convolve-fixed-point-batch");
+    let _ = std::fs::remove_dir_all(&directory);
+    std::fs::create_dir_all(&directory).unwrap();
+    let (source, target) = (directory.join("in.png"), directory.join("out.png"));
+    input.save(&source).unwrap();
+
+    batch::run(
+        [(source, target.clone())],
+        &kernels.map(KernelImpl::from),
+        Backend::FixedPoint,
+        options,
+        None,
+    )
+    .into_result()
+    .unwrap();
+    assert_eq!(image::open(&target).unwrap(), blurred);
+
+    std::fs::remove_dir_all(directory).unwrap();
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use clap::ValueEnum;
+use image::{DynamicImage, Rgb, Rgb32FImage, Rgba, Rgba32FImage};
+#[cfg(feature = "gpu")]
+use image_convolve::convolution::backends::gpu::offscreen::context::GpuDevice;
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// The other backends accumulate in `f32` instead of `f64`,
+/// which for 3x3 kernels on values in `0.0..=1.0` differs by a few ULPs.
+const TOLERANCE: f32 = 1e-5;
+
+/// The FFT spreads rounding errors over the whole image.
+const FFT_TOLERANCE: f32 = 1e-4;
+
+/// GPUs may evaluate with less precise, fused or reordered arithmetic.
+#[cfg(feature = "gpu")]
+const GPU_TOLERANCE: f32 = 1e-4;
+
+/// A single bright pixel, such that the output shows the (flipped) kernel itself.
+fn impulse() -> DynamicImage {
+    Rgb32FImage::from_fn(7, 7, |x, y| {
+        if (x, y) == (3, 3) {
+            Rgb([1., 0.5, 0.25])
+        } else {
+            Rgb([0., 0., 0.])
+        }
+    })
+    .into()
+}
+
+/// Ramps in different directions, such that every border mode gives different results.
+fn gradient() -> DynamicImage {
+    Rgb32FImage::from_fn(9, 6, |x, y| {
+        Rgb([x as f32 / 8., y as f32 / 5., (x + y) as f32 / 13.])
+    })
+    .into()
+}
+
+/// Alternating pixels, the worst case for blurs and edge detection.
+fn checkerboard() -> DynamicImage {
+    Rgb32FImage::from_fn(6, 5, |x, y| {
+        let value = ((x + y) % 2) as f32;
+        Rgb([value, 1. - value, value * 0.5])
+    })
+    .into()
+}
+
+/// Varying transparency, including fully transparent pixels.
+fn translucent() -> DynamicImage {
+    let colors = gradient().to_rgb32f();
+
+    Rgba32FImage::from_fn(9, 6, |x, y| {
+        let [red, green, blue] = colors.get_pixel(x, y).0;
+        Rgba([red, green, blue, ((x * 3 + y * 5) % 7) as f32 / 6.])
+    })
+    .into()
+}
+
+/// The options to check, sRGB first since it is the simplest.
+fn options() -> Vec<Options> {
+    let mut options = Vec::new();
+
+    for &border in BorderMode::value_variants() {
+        for color_space in [ColorSpace::Srgb, ColorSpace::Linear] {
+            for alpha in [AlphaMode::PassThrough, AlphaMode::Convolve] {
+                for premultiply in [false, true] {
+                    options.push(Options {
+                        border,
+                        border_color: [0.25, 0.5, 0.75, 0.5],
+                        alpha,
+                        premultiply,
+                        color_space,
+                        ..Options::default()
+                    });
+                }
+            }
+        }
+    }
+
+    options
+}
+
+#[test]
+fn reference_of_impulse_is_the_kernel() {
+    let options = Options {
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    };
+    let output = impulse()
+        .convolve(Kernel::Sharpen, Backend::Reference, options)
+        .unwrap()
+        .to_rgb32f();
+
+    // The kernel is symmetric, so flipping it does not matter.
+    let expected = KernelImpl::from(Kernel::Sharpen);
+    for (x, y, pixel) in output.enumerate_pixels() {
+        let (col, row) = (x as i64 - 2, y as i64 - 2);
+        let weight = if (0..3).contains(&col) && (0..3).contains(&row) {
+            expected.weight(col as u32, row as u32)
+        } else {
+            0.
+        };
+
+        assert_eq!(
+            pixel.0,
+            [weight, weight * 0.5, weight * 0.25],
+            "pixel ({x}, {y})"
+        );
+    }
+}
+
+#[test]
+fn reference_of_crop_matches_padded_interior() {
+    let input = gradient();
+    let options = Options {
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    };
+
+    let padded = input
+        .clone()
+        .convolve(Kernel::EdgeDetection2, Backend::Reference, options)
+        .unwrap();
+    let cropped = input
+        .convolve(
+            Kernel::EdgeDetection2,
+            Backend::Reference,
+            Options {
+                border: BorderMode::Crop,
+                ..options
+            },
+        )
+        .unwrap();
+
+    common::assert_close(&cropped, &padded.crop_imm(1, 1, 7, 4), 0.);
+}
+
+/// Every backend against the reference, for each kernel preset and combination of options.
+/// The GPU backend is skipped if there is no adapter.
+#[test]
+fn backends_match_reference() {
+    #[cfg(feature = "gpu")]
+    let gpu_available = match GpuDevice::shared() {
+        Ok(_) => true,
+        Err(Error::AdapterUnavailable) => {
+            eprintln!("No GPU adapter available, skipping the GPU backend");
+            false
+        }
+        Err(e) => panic!("failed to set up the GPU: {e}"),
+    };
+
+    for input in [impulse(), gradient(), checkerboard(), translucent()] {
+        for &kernel in Kernel::value_variants() {
+            for options in options() {
+                let expected = input
+                    .clone()
+                    .convolve(kernel, Backend::Reference, options)
+                    .unwrap();
+
+                for &backend in Backend::value_variants() {
+                    let tolerance = match backend {
+                        Backend::Fft => FFT_TOLERANCE,
+                        // Only supports 8 bit images, see `tests/fixed_point.rs`.
+                        Backend::FixedPoint => continue,
+                        #[cfg(feature = "gpu")]
+                        Backend::GpuOffscreen if !gpu_available => continue,
+                        #[cfg(feature = "gpu")]
+                        Backend::GpuOffscreen => GPU_TOLERANCE,
+                        _ => TOLERANCE,
+                    };
+
+                    let output = match input.clone().convolve(kernel, backend, options) {
+                        Ok(output) => output,
+                        // Only separable kernels are supported by the separable backends.
+                        Err(Error::UnsupportedKernel(_)) => continue,
+                        Err(e) => panic!("{backend:?} with {kernel:?} and {options:?}: {e}"),
+                    };
+
+                    common::assert_close_relative(&output, &expected, tolerance);
+                }
+            }
+        }
+    }
+}
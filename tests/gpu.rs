@@ -0,0 +1,140 @@
+#![cfg(feature = "gpu")]
+
+mod common;
+
+use common::test_image;
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// Without a GPU the backend must report it rather than panic,
+/// and with one it must convolve like the CPU backends.
+#[test]
+fn reports_missing_adapter() {
+    let input = test_image(8, 8);
+
+    match input
+        .clone()
+        .convolve(Kernel::BoxBlur, Backend::GpuOffscreen, Options::default())
+    {
+        Ok(output) => {
+            let expected = input
+                .convolve(
+                    Kernel::BoxBlur,
+                    Backend::SingleNestedLoops,
+                    Options::default(),
+                )
+                .unwrap();
+
+            common::assert_close(&output, &expected, 1e-5);
+        }
+        Err(Error::AdapterUnavailable) => {}
+        Err(e) => panic!("unexpected error: {e}"),
+    }
+}
+
+/// Indices left of and above the image are negative, which the GPU must wrap and mirror
+/// like the CPU. A length which is not a power of two shows if they are taken as unsigned.
+#[test]
+fn wraps_and_mirrors_negative_indices() {
+    let input = test_image(13, 9);
+    // Reads the pixel two to the left and two above the center.
+    let mut weights = vec![0.; 25];
+    weights[0] = 1.;
+    let kernel = KernelImpl::new(5, 5, weights, 1.).unwrap();
+
+    for border in [BorderMode::Wrap, BorderMode::Mirror] {
+        let options = Options {
+            border,
+            ..Options::default()
+        };
+        let expected = input
+            .clone()
+            .convolve(kernel.clone(), Backend::SingleNestedLoops, options)
+            .unwrap();
+
+        match input
+            .clone()
+            .convolve(kernel.clone(), Backend::GpuOffscreen, options)
+        {
+            Ok(output) => common::assert_close(&output, &expected, 1e-4),
+            Err(Error::AdapterUnavailable) => return,
+            Err(e) => panic!("{border:?}: {e}"),
+        }
+    }
+}
+
+/// The stages alternate between two textures, so odd and even numbers of stages
+/// are checked, including cropping which shrinks the image with every stage.
+#[test]
+fn pipelines_match_the_cpu() {
+    let input = test_image(23, 17);
+    let kernels: Vec<KernelImpl> = vec![
+        Kernel::GaussianBlur.into(),
+        Kernel::EdgeDetection1.into(),
+        KernelImpl::box_blur(3, 5).unwrap(),
+    ];
+
+    for border in [BorderMode::Clamp, BorderMode::Crop] {
+        let options = Options {
+            border,
+            ..Options::default()
+        };
+
+        for stages in 1..=kernels.len() {
+            let kernels = &kernels[..stages];
+            let expected = input
+                .clone()
+                .convolve_pipeline(kernels.to_vec(), Backend::SingleNestedLoops, options)
+                .unwrap();
+
+            match input
+                .clone()
+                .convolve_pipeline(kernels.to_vec(), Backend::GpuOffscreen, options)
+            {
+                Ok(output) => common::assert_close(&output, &expected, 1e-4),
+                Err(Error::AdapterUnavailable) => return,
+                Err(e) => panic!("{stages} stages with {border:?}: {e}"),
+            }
+        }
+    }
+}
+
+/// Threads share the device and its error scopes, which must not get mixed up.
+#[test]
+fn convolves_concurrently() {
+    let input = test_image(16, 16);
+    let expected = input
+        .clone()
+        .convolve(
+            Kernel::Sharpen,
+            Backend::SingleNestedLoops,
+            Options::default(),
+        )
+        .unwrap();
+
+    let outputs: Vec<_> = std::thread::scope(|scope| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                scope.spawn(|| {
+                    input.clone().convolve(
+                        Kernel::Sharpen,
+                        Backend::GpuOffscreen,
+                        Options::default(),
+                    )
+                })
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .collect()
+    });
+
+    for output in outputs {
+        match output {
+            Ok(output) => common::assert_close(&output, &expected, 1e-4),
+            Err(Error::AdapterUnavailable) => return,
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{assert_close, run, test_image};
+use image::{DynamicImage, RgbImage};
+use image_convolve::{convolution::backends::cpu, prelude::*};
+
+const CPU_BACKENDS: &[Backend] = &[
+    Backend::Auto,
+    Backend::SingleNestedLoops,
+    Backend::SingleNestedIterators,
+    Backend::SingleSimd,
+    Backend::SingleBlocked,
+    #[cfg(feature = "rayon")]
+    Backend::MultiRayon,
+    #[cfg(feature = "rayon")]
+    Backend::MultiChunked,
+    #[cfg(feature = "rayon")]
+    Backend::MultiBlocked,
+    Backend::SingleSeparable,
+    #[cfg(feature = "rayon")]
+    Backend::MultiSeparable,
+    Backend::Fft,
+];
+
+#[test]
+fn matches_driving_the_backend() {
+    let input = test_image(24, 17);
+    let expected = run(cpu::single::NestedLoops::from((
+        input.clone(),
+        Kernel::GaussianBlur,
+    )));
+
+    for &backend in CPU_BACKENDS {
+        let output = input
+            .clone()
+            .convolve(Kernel::GaussianBlur, backend, Options::default())
+            .unwrap();
+
+        assert_close(&output, &expected, 1e-5);
+    }
+}
+
+#[test]
+fn accepts_image_buffers() {
+    let input = RgbImage::from_fn(8, 8, |x, y| image::Rgb([x as u8 * 30, y as u8 * 30, 128]));
+    let expected = DynamicImage::from(input.clone())
+        .convolve(Kernel::Sharpen, Backend::Auto, Options::default())
+        .unwrap();
+
+    let output = input
+        .convolve(Kernel::Sharpen, Backend::Auto, Options::default())
+        .unwrap();
+
+    assert_eq!(output, expected);
+    assert_eq!(output.color(), image::ColorType::Rgb8);
+}
+
+#[test]
+fn reports_unsupported_kernels() {
+    let result = test_image(8, 8).convolve(
+        Kernel::EdgeDetection2,
+        Backend::SingleSeparable,
+        Options::default(),
+    );
+
+    assert!(matches!(result, Err(Error::UnsupportedKernel(_))));
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use common::{run, test_image};
+use image::GenericImageView;
+use image_convolve::{convolution::backends::cpu, kernel::KernelImpl, prelude::*};
+
+/// No color space conversion, such that an impulse reproduces the input exactly.
+fn srgb(border: BorderMode) -> Options {
+    Options {
+        border,
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    }
+}
+
+/// A kernel of the given size whose only weight is one at `(col, row)`.
+fn impulse(width: u32, height: u32, col: u32, row: u32) -> KernelImpl {
+    let mut weights = vec![0.; (width * height) as usize];
+    weights[(col + row * width) as usize] = 1.;
+
+    KernelImpl::new(width, height, weights, 1.).unwrap()
+}
+
+#[test]
+fn accepts_odd_sizes() {
+    for (width, height) in [(1, 1), (3, 3), (5, 5), (7, 7), (7, 3), (1, 9)] {
+        let weights = (0..width * height).map(|i| i as f32).collect();
+        let kernel = KernelImpl::new(width, height, weights, 1.).unwrap();
+
+        assert_eq!((kernel.width(), kernel.height()), (width, height));
+        assert_eq!(kernel.radius(), (width / 2, height / 2));
+        // Weights are stored row by row.
+        assert_eq!(kernel.weight(width - 1, 0), (width - 1) as f32);
+        assert_eq!(kernel.weight(0, height - 1), ((height - 1) * width) as f32);
+    }
+}
+
+#[test]
+fn rejects_even_and_zero_sizes() {
+    for (width, height) in [(0, 0), (0, 3), (3, 0), (2, 2), (4, 5), (5, 4)] {
+        assert!(
+            matches!(
+                KernelImpl::new(width, height, vec![1.; (width * height) as usize], 1.),
+                Err(Error::InvalidKernel(_))
+            ),
+            "{width}x{height}"
+        );
+    }
+}
+
+#[test]
+fn rejects_wrong_number_of_weights() {
+    assert!(matches!(
+        KernelImpl::new(5, 5, vec![1.; 24], 1.),
+        Err(Error::InvalidKernel(_))
+    ));
+    // The number of weights overflows.
+    assert!(matches!(
+        KernelImpl::new(65_537, 65_537, vec![1.; 1], 1.),
+        Err(Error::InvalidKernel(_))
+    ));
+}
+
+#[test]
+fn centered_impulse_is_identity() {
+    let image = test_image(16, 12);
+
+    for size in [5, 7] {
+        let kernel = impulse(size, size, size / 2, size / 2);
+        let output = run(cpu::single::NestedLoops::from((
+            image.clone(),
+            kernel,
+            srgb(BorderMode::Clamp),
+        )));
+
+        assert_eq!(output.to_rgba32f(), image.to_rgba32f(), "{size}x{size}");
+    }
+}
+
+#[test]
+fn off_center_impulse_shifts() {
+    let image = test_image(16, 12);
+    let options = srgb(BorderMode::Crop);
+
+    // The top-left weight of a 7x7 kernel reads 3 pixels up and left of the center.
+    let output = run(cpu::single::NestedLoops::from((
+        image.clone(),
+        impulse(7, 7, 0, 0),
+        options,
+    )));
+
+    assert_eq!(output.dimensions(), (10, 6));
+    let (output, image) = (output.to_rgba32f(), image.to_rgba32f());
+    for (x, y, pixel) in output.enumerate_pixels() {
+        assert_eq!(pixel, image.get_pixel(x, y), "({x}, {y})");
+    }
+}
+
+#[test]
+fn gaussian_weights_sum_to_one() {
+    for (sigma, radius) in [
+        (0.5, None),
+        (1., None),
+        (2.5, None),
+        (3., Some(2)),
+        (1., Some(20)),
+    ] {
+        let kernel = KernelImpl::gaussian(sigma, radius).unwrap();
+        let sum: f32 = kernel.weights().iter().sum::<f32>() * kernel.normalization();
+
+        assert!(
+            (sum - 1.).abs() < 1e-5,
+            "sigma {sigma}, radius {radius:?}: {sum}"
+        );
+    }
+}
+
+#[test]
+fn gaussian_is_symmetric() {
+    let kernel = KernelImpl::gaussian(1.7, None).unwrap();
+    let last = kernel.width() - 1;
+    assert_eq!(kernel.width(), kernel.height());
+
+    for row in 0..kernel.height() {
+        for col in 0..kernel.width() {
+            let weight = kernel.weight(col, row);
+
+            assert_eq!(weight, kernel.weight(last - col, row));
+            assert_eq!(weight, kernel.weight(col, last - row));
+            assert_eq!(weight, kernel.weight(row, col));
+        }
+    }
+
+    // The center is the largest weight.
+    let center = kernel.weight(last / 2, last / 2);
+    assert!(kernel.weights().iter().all(|&weight| weight <= center));
+}
+
+#[test]
+fn gaussian_radius_defaults_to_three_sigma() {
+    assert_eq!(KernelImpl::gaussian(2., None).unwrap().radius(), (6, 6));
+    assert_eq!(KernelImpl::gaussian(0.9, None).unwrap().radius(), (3, 3));
+    assert_eq!(KernelImpl::gaussian(5., Some(0)).unwrap().radius(), (0, 0));
+}
+
+#[test]
+fn gaussian_rejects_invalid_parameters() {
+    let max = KernelImpl::MAX_GAUSSIAN_RADIUS;
+
+    for (sigma, radius) in [
+        (0., None),
+        (-1., None),
+        (f32::NAN, None),
+        (f32::INFINITY, None),
+        (1., Some(max + 1)),
+        (1., Some(u32::MAX / 2 + 1)),
+        (1., Some(u32::MAX)),
+        (1e10, None),
+        (f32::MAX, None),
+    ] {
+        assert!(
+            matches!(
+                KernelImpl::gaussian(sigma, radius),
+                Err(Error::InvalidKernel(_))
+            ),
+            "sigma {sigma}, radius {radius:?}"
+        );
+    }
+}
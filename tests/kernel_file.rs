@@ -0,0 +1,99 @@
+use clap::Parser;
+use image_convolve::{kernel::KernelImpl, prelude::*};
+
+/// The line and column of a parse error.
+fn error_position(s: &str) -> (usize, usize) {
+    match s.parse::<KernelImpl>() {
+        Err(Error::KernelParse { line, column, .. }) => (line, column),
+        other => panic!("expected a parse error, got {other:?}"),
+    }
+}
+
+#[test]
+fn parses_weights_and_settings() {
+    let kernel: KernelImpl = "
+        # 3x3 blur, with a bias
+        divisor 16
+        bias: 0.25
+
+        1, 2, 1
+        2  4  2  # the center
+        1,2,1
+    "
+    .parse()
+    .unwrap();
+
+    let expected = KernelImpl::new(3, 3, vec![1., 2., 1., 2., 4., 2., 1., 2., 1.], 1. / 16.)
+        .unwrap()
+        .with_bias(0.25);
+    assert_eq!(kernel, expected);
+}
+
+#[test]
+fn parses_non_square_kernels_and_normalization() {
+    let kernel: KernelImpl = "normalization 0.5\n1 2 3 4 5".parse().unwrap();
+
+    assert_eq!(
+        kernel,
+        KernelImpl::new(5, 1, vec![1., 2., 3., 4., 5.], 0.5).unwrap()
+    );
+}
+
+#[test]
+fn reports_bad_tokens() {
+    assert_eq!(error_position("1 1 1\n1 x 1\n1 1 1"), (2, 3));
+    assert_eq!(error_position("divisor two"), (1, 9));
+    assert_eq!(error_position("divisor 0"), (1, 9));
+    assert_eq!(error_position("bias 1 2"), (1, 8));
+}
+
+#[test]
+fn reports_missing_and_repeated_settings() {
+    assert_eq!(error_position("  divisor"), (1, 10));
+    assert_eq!(error_position("bias 1\n1\nbias 2"), (3, 1));
+}
+
+#[test]
+fn reports_ragged_rows() {
+    assert_eq!(error_position("1 1 1\n1 1 1\n  1 1"), (3, 3));
+}
+
+#[test]
+fn reports_empty_descriptions() {
+    assert_eq!(error_position(""), (1, 1));
+    assert_eq!(error_position("# nothing\ndivisor 2\n"), (2, 1));
+}
+
+#[test]
+fn columns_count_characters() {
+    // The ideographic space is a single character, but three bytes.
+    assert_eq!(error_position("1\u{3000}1\u{3000}x"), (1, 5));
+    assert_eq!(error_position("\u{3000}\u{3000}divisor"), (1, 10));
+    assert_eq!(error_position("bias\u{3000}é"), (1, 6));
+}
+
+#[test]
+fn reports_even_sizes() {
+    // Even widths point at the first row, even heights at the last.
+    assert_eq!(error_position("# even\n  1 1\n  1 1"), (2, 3));
+    assert_eq!(error_position("divisor 6\n1 1 1\n 1 1 1"), (3, 2));
+    assert_eq!(error_position("\n\n1 1 1 1"), (3, 1));
+}
+
+#[test]
+fn cli_rejects_gaussian_options_with_kernel_files() {
+    let parse = |args: &[&str]| {
+        let required = [
+            "image-convolve",
+            "-i=in.png",
+            "-o=out.png",
+            "-b=auto",
+            "--kernel-file=kernel.txt",
+        ];
+        Cli::try_parse_from(required.iter().chain(args))
+    };
+
+    assert!(parse(&[]).is_ok());
+    assert!(parse(&["--sigma=2"]).is_err());
+    assert!(parse(&["--sigma=2", "--radius=3"]).is_err());
+}
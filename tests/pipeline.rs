@@ -0,0 +1,77 @@
+mod common;
+
+use clap::Parser;
+use common::{assert_close, test_image};
+use image::{ColorType, DynamicImage};
+use image_convolve::prelude::*;
+
+#[test]
+fn runs_stages_in_order() {
+    let input = test_image(24, 17);
+    let options = Options::default();
+
+    let expected = input
+        .clone()
+        .convolve(Kernel::GaussianBlur, Backend::Auto, options)
+        .and_then(|image| image.convolve(Kernel::EdgeDetection1, Backend::Auto, options))
+        .unwrap();
+
+    let output = input
+        .convolve_pipeline(
+            [Kernel::GaussianBlur, Kernel::EdgeDetection1],
+            Backend::Auto,
+            options,
+        )
+        .unwrap();
+
+    // The pipeline stays in linear light between stages, which only rounds differently.
+    assert_close(&output, &expected, 1e-5);
+}
+
+#[test]
+fn does_not_quantize_between_stages() {
+    let input = DynamicImage::from(test_image(24, 17).to_rgb8());
+    let kernels = [Kernel::BoxBlur, Kernel::Sharpen];
+
+    let output = input
+        .clone()
+        .convolve_pipeline(kernels, Backend::Auto, Options::default())
+        .unwrap();
+    assert_eq!(output.color(), ColorType::Rgb8);
+
+    let float = DynamicImage::from(input.to_rgb32f())
+        .convolve_pipeline(kernels, Backend::Auto, Options::default())
+        .unwrap();
+    assert_eq!(output, DynamicImage::from(float.to_rgb8()));
+}
+
+#[test]
+fn empty_pipeline_is_identity() {
+    let input = test_image(8, 8);
+    let output = input
+        .clone()
+        .convolve_pipeline(Vec::<Kernel>::new(), Backend::Auto, Options::default())
+        .unwrap();
+
+    assert_eq!(output, input);
+}
+
+#[test]
+fn cli_accepts_kernel_lists() {
+    let args = Cli::try_parse_from([
+        "image-convolve",
+        "-i=in.png",
+        "-o=out.png",
+        "-b=auto",
+        "--kernel=gaussian-blur,sharpen",
+        "--kernel=edge-detection2",
+        "--sigma=2",
+    ])
+    .unwrap();
+
+    let kernels = args.load_kernels().unwrap();
+    assert_eq!(kernels.len(), 3);
+    assert_eq!(kernels[0].width(), 13);
+    assert_eq!(kernels[1], Kernel::Sharpen.into());
+    assert_eq!(kernels[2], Kernel::EdgeDetection2.into());
+}
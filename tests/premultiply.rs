@@ -0,0 +1,121 @@
+mod common;
+
+use common::{assert_close, run};
+use image::{DynamicImage, Rgba, Rgba32FImage};
+use image_convolve::{convolution::backends::cpu, kernel::KernelImpl, prelude::*};
+
+/// An opaque red square in the middle with hard edges, surrounded by fully transparent pixels.
+/// The transparent pixels are green, which must never show up in the output.
+fn sprite() -> DynamicImage {
+    Rgba32FImage::from_fn(16, 16, |x, y| {
+        if (4..12).contains(&x) && (4..12).contains(&y) {
+            Rgba([1., 0., 0., 1.])
+        } else {
+            Rgba([0., 1., 0., 0.])
+        }
+    })
+    .into()
+}
+
+fn options(alpha: AlphaMode) -> Options {
+    Options {
+        alpha,
+        premultiply: true,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn straight_alpha_blur_has_fringes() {
+    let options = Options {
+        alpha: AlphaMode::Convolve,
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    };
+    let output = run(cpu::single::NestedLoops::from((
+        sprite(),
+        Kernel::BoxBlur,
+        options,
+    )))
+    .to_rgba32f();
+
+    // Just outside the edge, the transparent green bleeds into the red:
+    // 3 of 9 neighbours are red and 6 are green.
+    let fringe = output.get_pixel(3, 8);
+    assert!(fringe[0] < 0.5 && fringe[1] > 0.5, "{fringe:?}");
+}
+
+#[test]
+fn premultiplied_blur_has_no_fringes() {
+    let output = run(cpu::single::NestedLoops::from((
+        sprite(),
+        Kernel::BoxBlur,
+        options(AlphaMode::Convolve),
+    )))
+    .to_rgba32f();
+
+    for (x, y, pixel) in output.enumerate_pixels() {
+        if pixel[3] > 0. {
+            assert!((pixel[0] - 1.).abs() < 1e-6, "({x}, {y}): {pixel:?}");
+            assert!(pixel[1].abs() < 1e-6, "({x}, {y}): {pixel:?}");
+        }
+    }
+
+    // Just outside the edge: 3 of 9 neighbours are opaque.
+    assert!((output.get_pixel(3, 8)[3] - 3. / 9.).abs() < 1e-6);
+}
+
+#[test]
+fn fully_transparent_pixels_are_black() {
+    for alpha in [AlphaMode::PassThrough, AlphaMode::Convolve] {
+        let output = run(cpu::single::NestedLoops::from((
+            sprite(),
+            Kernel::GaussianBlur,
+            options(alpha),
+        )))
+        .to_rgba32f();
+
+        assert_eq!(*output.get_pixel(0, 0), Rgba([0., 0., 0., 0.]));
+        assert!(output
+            .pixels()
+            .all(|pixel| pixel.0.iter().all(|c| c.is_finite())));
+    }
+}
+
+#[test]
+fn backends_agree() {
+    // A kernel with compact support, such that transparent regions stay exactly transparent.
+    // Otherwise tiny alphas amplify rounding differences between backends when unpremultiplying.
+    let kernel = KernelImpl::new(5, 5, vec![1.; 25], 1. / 25.).unwrap();
+
+    for alpha in [AlphaMode::PassThrough, AlphaMode::Convolve] {
+        let options = options(alpha);
+        let expected = run(cpu::single::NestedLoops::from((
+            sprite(),
+            kernel.clone(),
+            options,
+        )));
+
+        let outputs = [
+            run(cpu::single::NestedIterators::from((
+                sprite(),
+                kernel.clone(),
+                options,
+            ))),
+            #[cfg(feature = "rayon")]
+            run(cpu::multi::NestedIterators::from((
+                sprite(),
+                kernel.clone(),
+                options,
+            ))),
+            run(cpu::separable::Single::try_from((sprite(), kernel.clone(), options)).unwrap()),
+            #[cfg(feature = "rayon")]
+            run(cpu::separable::Multi::try_from((sprite(), kernel.clone(), options)).unwrap()),
+            run(cpu::fft::Fft::from((sprite(), kernel.clone(), options))),
+        ];
+
+        for output in outputs {
+            assert_close(&output, &expected, 1e-4);
+        }
+    }
+}
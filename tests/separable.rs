@@ -0,0 +1,79 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{assert_close, run, test_image};
+use image_convolve::{convolution::backends::cpu, kernel::KernelImpl, prelude::*};
+
+fn separable_kernels() -> Vec<KernelImpl> {
+    vec![
+        Kernel::Identity.into(),
+        Kernel::BoxBlur.into(),
+        Kernel::GaussianBlur.into(),
+        KernelImpl::gaussian(1.5, None).unwrap(),
+        // Non-square, with a bias
+        KernelImpl::new(5, 3, (0..15).map(|i| (i % 5) as f32 - 2.).collect(), 0.5)
+            .unwrap()
+            .with_bias(0.25),
+    ]
+}
+
+#[test]
+fn detects_separable_kernels() {
+    for kernel in separable_kernels() {
+        let (horizontal, vertical) = kernel.separate().expect("separable");
+        assert_eq!(horizontal.len(), kernel.width() as usize);
+        assert_eq!(vertical.len(), kernel.height() as usize);
+    }
+}
+
+#[test]
+fn rejects_non_separable_kernels() {
+    for kernel in [
+        Kernel::EdgeDetection1,
+        Kernel::EdgeDetection2,
+        Kernel::Sharpen,
+    ] {
+        assert!(KernelImpl::from(kernel).separate().is_none(), "{kernel}");
+        assert!(matches!(
+            cpu::separable::Single::try_from((test_image(8, 8), kernel)),
+            Err(Error::UnsupportedKernel(_))
+        ));
+    }
+}
+
+#[test]
+fn matches_2d_convolution() {
+    let image = test_image(37, 23);
+
+    for kernel in separable_kernels() {
+        for &border in BorderMode::value_variants() {
+            let options = Options {
+                border,
+                border_color: [0.2, 0.4, 0.6, 1.0],
+                ..Options::default()
+            };
+
+            let expected = run(cpu::single::NestedLoops::from((
+                image.clone(),
+                kernel.clone(),
+                options,
+            )));
+            let single =
+                run(
+                    cpu::separable::Single::try_from((image.clone(), kernel.clone(), options))
+                        .unwrap(),
+                );
+            assert_close(&single, &expected, 1e-5);
+
+            #[cfg(feature = "rayon")]
+            {
+                let multi =
+                    run(
+                        cpu::separable::Multi::try_from((image.clone(), kernel.clone(), options))
+                            .unwrap(),
+                    );
+                assert_close(&multi, &expected, 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{kernels, run, test_image};
+use image_convolve::{
+    convolution::backends::cpu::{self, simd::InstructionSet},
+    prelude::*,
+};
+
+/// Every instruction set, of which those the CPU does not support are skipped.
+const INSTRUCTION_SETS: &[InstructionSet] = &[
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    InstructionSet::Avx,
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    InstructionSet::Sse,
+    InstructionSet::Scalar,
+];
+
+#[test]
+fn matches_nested_loops_exactly() {
+    // Row lengths which are and are not multiples of the SIMD lanes.
+    for width in [1, 2, 3, 8, 13, 31] {
+        let image = test_image(width, 11);
+
+        for kernel in kernels() {
+            for &border in BorderMode::value_variants() {
+                let options = Options {
+                    border,
+                    ..Options::default()
+                };
+                if options.check_dimensions((width, 11), &kernel).is_err() {
+                    continue;
+                }
+
+                let expected = run(cpu::single::NestedLoops::from((
+                    image.clone(),
+                    kernel.clone(),
+                    options,
+                )));
+
+                for &instruction_set in INSTRUCTION_SETS {
+                    let Ok(simd) = cpu::simd::Simd::from((image.clone(), kernel.clone(), options))
+                        .with_instruction_set(instruction_set)
+                    else {
+                        continue;
+                    };
+
+                    assert_eq!(
+                        run(simd).to_rgba32f().into_raw(),
+                        expected.to_rgba32f().into_raw(),
+                        "width {width}, {border:?}, {instruction_set:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rejects_unsupported_instruction_sets() {
+    for &instruction_set in INSTRUCTION_SETS {
+        let simd = cpu::simd::Simd::from((test_image(4, 4), Kernel::Sharpen))
+            .with_instruction_set(instruction_set);
+
+        assert_eq!(simd.is_ok(), instruction_set.is_supported());
+    }
+
+    assert!(InstructionSet::detect().is_supported());
+}
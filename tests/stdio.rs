@@ -0,0 +1,72 @@
+mod common;
+
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use common::test_image;
+use image::{DynamicImage, ImageFormat};
+use image_convolve::{
+    convolution::strategy::{decode, write_to},
+    prelude::*,
+};
+
+fn png(image: &DynamicImage) -> Vec<u8> {
+    let mut bytes = vec![];
+    write_to(image, &mut bytes, ImageFormat::Png).unwrap();
+    bytes
+}
+
+#[test]
+fn round_trips_through_memory() {
+    let image = DynamicImage::from(test_image(8, 8).to_rgba16());
+    let decoded = decode(&png(&image)).unwrap();
+
+    assert_eq!(decoded, image);
+}
+
+#[test]
+fn converts_color_types_the_format_lacks() {
+    let image = test_image(8, 8);
+
+    let mut bytes = vec![];
+    write_to(&image, &mut bytes, ImageFormat::Jpeg).unwrap();
+
+    let decoded = decode(&bytes).unwrap();
+    assert_eq!(decoded.color(), image::ColorType::Rgb8);
+}
+
+#[test]
+fn pipes_through_stdin_and_stdout() {
+    let input = DynamicImage::from(test_image(16, 16).to_rgb8());
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_image-convolve"))
+        .args(["-i", "-", "-o", "-", "-k", "sharpen", "-b", "auto"])
+        .args(["--format", "png"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&png(&input)).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let expected = input
+        .convolve(Kernel::Sharpen, Backend::Auto, Options::default())
+        .unwrap();
+    assert_eq!(decode(&output.stdout).unwrap(), expected);
+}
+
+#[test]
+fn stdout_needs_a_format() {
+    let output = Command::new(env!("CARGO_BIN_EXE_image-convolve"))
+        .args(["-i", "-", "-o", "-", "-k", "sharpen", "-b", "auto"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,259 @@
+mod common;
+
+use clap::{Parser, ValueEnum};
+use common::{assert_close, run, test_image};
+use image::GenericImageView;
+use image_convolve::{
+    convolution::backends::{cpu::summed_area::SummedArea, reference::Reference},
+    kernel::KernelImpl,
+    prelude::*,
+};
+
+const TOLERANCE: f32 = 1e-5;
+
+fn boxes() -> Vec<KernelImpl> {
+    vec![
+        KernelImpl::box_blur(1, 1).unwrap(),
+        Kernel::BoxBlur.into(),
+        KernelImpl::box_blur(7, 3).unwrap(),
+        KernelImpl::box_blur(25, 25).unwrap(),
+        // Equal weights need not sum to one, and may have a bias.
+        KernelImpl::new(3, 5, vec![2.; 15], 0.02)
+            .unwrap()
+            .with_bias(0.25),
+    ]
+}
+
+#[test]
+fn matches_reference() {
+    let image = test_image(41, 29);
+
+    for kernel in boxes() {
+        for &border in BorderMode::value_variants() {
+            let options = Options {
+                border,
+                border_color: [0.25, 0.5, 0.75, 1.],
+                ..Options::default()
+            };
+            if options
+                .check_dimensions(image.dimensions(), &kernel)
+                .is_err()
+            {
+                continue;
+            }
+
+            let expected = run(Reference::from((image.clone(), kernel.clone(), options)));
+            let output =
+                run(SummedArea::try_from((image.clone(), kernel.clone(), options)).unwrap());
+
+            assert_close(&output, &expected, TOLERANCE);
+        }
+    }
+}
+
+#[test]
+fn passes_match_a_pipeline() {
+    let image = test_image(41, 29);
+    let kernel = KernelImpl::box_blur(9, 5).unwrap();
+
+    // Including cropping, which shrinks the image with every pass.
+    for &border in BorderMode::value_variants() {
+        let options = Options {
+            border,
+            ..Options::default()
+        };
+
+        let expected = Backend::Reference
+            .convolve_pipeline(image.clone(), vec![kernel.clone(); 3], options)
+            .unwrap();
+        let output = run(
+            SummedArea::try_from((image.clone(), kernel.clone(), options))
+                .unwrap()
+                .with_passes(3),
+        );
+
+        assert_close(&output, &expected, TOLERANCE);
+    }
+}
+
+#[test]
+fn three_passes_approximate_a_gaussian() {
+    // Three passes of a box of width 2r+1 have the variance of a Gaussian with sigma^2 = r(r+1).
+    let image = test_image(64, 48);
+    let options = Options {
+        border: BorderMode::Wrap,
+        ..Options::default()
+    };
+
+    let kernel = KernelImpl::box_blur(9, 9).unwrap();
+
+    let boxes = run(SummedArea::try_from((image.clone(), kernel, options))
+        .unwrap()
+        .with_passes(3));
+    let gaussian = image
+        .convolve(
+            KernelImpl::gaussian(20f32.sqrt(), Some(12)).unwrap(),
+            Backend::Reference,
+            options,
+        )
+        .unwrap();
+
+    assert_close(&boxes, &gaussian, 0.02);
+}
+
+#[test]
+fn rejects_unequal_weights() {
+    assert!(matches!(
+        SummedArea::try_from((test_image(8, 8), Kernel::GaussianBlur)),
+        Err(Error::UnsupportedKernel(_))
+    ));
+}
+
+#[test]
+fn reports_images_too_small_for_every_pass() {
+    let options = Options {
+        border: BorderMode::Crop,
+        ..Options::default()
+    };
+    let mut backend = SummedArea::try_from((test_image(12, 12), Kernel::BoxBlur, options))
+        .unwrap()
+        .with_passes(6);
+
+    assert!(matches!(
+        backend.convolve(),
+        Err(Error::ImageTooSmall { .. })
+    ));
+}
+
+#[test]
+fn cli_repeats_box_blurs() {
+    let args = Cli::try_parse_from([
+        "image-convolve",
+        "-i=in.png",
+        "-o=out.png",
+        "-b=summed-area",
+        "--kernel=box-blur",
+        "--box-size=25x15",
+        "--passes=3",
+    ])
+    .unwrap();
+
+    let kernels = args.load_kernels().unwrap();
+    assert_eq!(kernels, vec![KernelImpl::box_blur(25, 15).unwrap(); 3]);
+}
+
+#[test]
+fn pipelines_convolve_repeated_kernels_as_passes() {
+    let image = test_image(41, 29);
+    let (blur, wide) = (
+        KernelImpl::box_blur(5, 3).unwrap(),
+        KernelImpl::box_blur(9, 1).unwrap(),
+    );
+    let options = Options {
+        color_space: ColorSpace::Srgb,
+        ..Options::default()
+    };
+
+    let passes = run(SummedArea::try_from((image.clone(), blur.clone(), options))
+        .unwrap()
+        .with_passes(3));
+    let expected = run(SummedArea::try_from((passes, wide.clone(), options)).unwrap());
+
+    let output = Backend::SummedArea
+        .convolve_pipeline(image, [blur.clone(), blur.clone(), blur, wide], options)
+        .unwrap();
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn cli_rejects_merging_for_summed_area() {
+    let parse = |args: &[&str]| {
+        let required = [
+            "image-convolve",
+            "-i=in.png",
+            "-o=out.png",
+            "--kernel=box-blur",
+        ];
+        Cli::try_parse_from(required.iter().chain(args))
+            .unwrap()
+            .load_kernels()
+    };
+
+    assert!(matches!(
+        parse(&["-b=summed-area", "--passes=3", "--merge-kernels"]),
+        Err(Error::InvalidKernel(_))
+    ));
+    assert_eq!(
+        parse(&["-b=summed-area", "--merge-kernels"]).unwrap(),
+        vec![Kernel::BoxBlur.into()]
+    );
+    assert_eq!(
+        parse(&["-b=auto", "--passes=3", "--merge-kernels"])
+            .unwrap()
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn cli_rejects_misplaced_box_sizes() {
+    let parse = |box_size: &str, kernel: &str| {
+        Cli::try_parse_from([
+            "image-convolve",
+            "-i=in.png",
+            "-o=out.png",
+            "-b=summed-area",
+            &format!("--kernel={kernel}"),
+            &format!("--box-size={box_size}"),
+        ])
+        .map_err(|_| ())
+        .and_then(|args| args.load_kernels().map_err(|_| ()))
+    };
+
+    assert_eq!(parse("51", "box-blur").unwrap()[0].width(), 51);
+    assert!(parse("50", "box-blur").is_err());
+    assert!(parse("5x", "box-blur").is_err());
+    assert!(parse("5", "sharpen").is_err());
+
+    // Kernel files are not changed by the box size, so it is rejected rather than ignored.
+    assert!(Cli::try_parse_from([
+        "image-convolve",
+        "-i=in.png",
+        "-o=out.png",
+        "-b=summed-area",
+        "--kernel-file=kernel.txt",
+        "--box-size=5",
+    ])
+    .is_err());
+}
+
+/// Rejected before the weights are allocated, which would abort the process.
+#[test]
+fn rejects_oversized_boxes() {
+    let max = KernelImpl::MAX_BOX_SIZE;
+    assert_eq!(KernelImpl::box_blur(max, 1).unwrap().width(), max);
+
+    for (width, height) in [(max + 2, 1), (1, max + 2), (99_999, 99_999), (u32::MAX, 1)] {
+        assert!(
+            matches!(
+                KernelImpl::box_blur(width, height),
+                Err(Error::InvalidKernel(_))
+            ),
+            "{width}x{height}"
+        );
+    }
+
+    let parse = |box_size: &str| {
+        Cli::try_parse_from([
+            "image-convolve",
+            "-i=in.png",
+            "-o=out.png",
+            "-b=summed-area",
+            "--kernel=box-blur",
+            &format!("--box-size={box_size}"),
+        ])
+    };
+    assert!(parse(&max.to_string()).is_ok());
+    assert!(parse("99999").is_err());
+    assert!(parse(&format!("3x{}", max + 2)).is_err());
+}
@@ -0,0 +1,191 @@
+mod common;
+
+use clap::{Parser, ValueEnum};
+use common::test_image;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use image_convolve::{
+    convolution::backends::cpu::tiled::Tiled, convolution::strategy::run, kernel::KernelImpl,
+    prelude::*,
+};
+
+const BACKENDS: &[Backend] = &[
+    Backend::SingleNestedLoops,
+    Backend::SingleNestedIterators,
+    Backend::SingleSimd,
+    Backend::SingleBlocked,
+    #[cfg(feature = "rayon")]
+    Backend::MultiRayon,
+    #[cfg(feature = "rayon")]
+    Backend::MultiChunked,
+    #[cfg(feature = "rayon")]
+    Backend::MultiBlocked,
+    Backend::SingleSeparable,
+    #[cfg(feature = "rayon")]
+    Backend::MultiSeparable,
+];
+
+/// Budgets giving strips of a single row, several rows and the whole image for the kernels below.
+const BUDGETS: [usize; 4] = [0, 30_000, 45_000, usize::MAX];
+
+/// A 16 bit image with varying transparency.
+fn translucent_image() -> DynamicImage {
+    let colors = test_image(24, 17).to_rgba16();
+
+    ImageBuffer::from_fn(24, 17, |x, y| {
+        let [red, green, blue, _] = colors.get_pixel(x, y).0;
+        Rgba::<u16>([red, green, blue, ((x * 2900 + y * 1300) % 65536) as u16])
+    })
+    .into()
+}
+
+fn assert_identical(input: &DynamicImage, kernel: &KernelImpl, options: Options) {
+    for &backend in BACKENDS {
+        let untiled = input
+            .clone()
+            .convolve(kernel.clone(), backend, options)
+            .unwrap();
+
+        for budget in BUDGETS {
+            let tiled =
+                run(Tiled::new(input.clone(), kernel.clone(), options, backend, budget).unwrap())
+                    .unwrap();
+
+            assert!(
+                tiled == untiled,
+                "{backend:?} with {options:?} and a budget of {budget} differs"
+            );
+        }
+    }
+}
+
+#[test]
+fn identical_to_untiled() {
+    let kernel = KernelImpl::gaussian(1.5, None).unwrap();
+
+    for &border in BorderMode::value_variants() {
+        let options = Options {
+            border,
+            border_color: [0.2, 0.4, 0.6, 0.8],
+            ..Options::default()
+        };
+
+        assert_identical(&test_image(24, 17), &kernel, options);
+    }
+}
+
+#[test]
+fn identical_to_untiled_with_alpha() {
+    let kernel = KernelImpl::new(3, 5, vec![1.; 15], 1. / 15.).unwrap();
+
+    for alpha in [AlphaMode::PassThrough, AlphaMode::Convolve] {
+        for premultiply in [false, true] {
+            let options = Options {
+                border: BorderMode::Wrap,
+                alpha,
+                premultiply,
+                ..Options::default()
+            };
+
+            assert_identical(&translucent_image(), &kernel, options);
+        }
+    }
+}
+
+#[test]
+fn used_by_backends_given_a_budget() {
+    let input = test_image(24, 17);
+    let options = Options {
+        memory_budget: Some(0),
+        ..Options::default()
+    };
+
+    let untiled = input
+        .clone()
+        .convolve(Kernel::EdgeDetection2, Backend::Auto, Options::default())
+        .unwrap();
+    let tiled = input
+        .convolve(Kernel::EdgeDetection2, Backend::Auto, options)
+        .unwrap();
+
+    assert!(tiled == untiled);
+}
+
+#[test]
+fn rejects_backends_without_strips() {
+    for backend in [
+        Backend::Fft,
+        Backend::Reference,
+        #[cfg(feature = "gpu")]
+        Backend::GpuOffscreen,
+    ] {
+        let result = Tiled::new(
+            test_image(8, 8),
+            Kernel::BoxBlur,
+            Options::default(),
+            backend,
+            0,
+        );
+
+        assert!(matches!(result, Err(Error::UnsupportedBackend(_))));
+    }
+}
+
+/// The images in between the stages would be held whole, which the budget is meant to avoid.
+#[test]
+fn pipelines_reject_budgets() {
+    let options = Options {
+        memory_budget: Some(0),
+        ..Options::default()
+    };
+
+    let result = test_image(8, 8).convolve_pipeline(
+        [Kernel::BoxBlur, Kernel::Sharpen],
+        Backend::SingleNestedLoops,
+        options,
+    );
+    assert!(matches!(result, Err(Error::UnsupportedBackend(_))));
+
+    let single = test_image(8, 8)
+        .convolve_pipeline([Kernel::BoxBlur], Backend::SingleNestedLoops, options)
+        .unwrap();
+    let untiled = test_image(8, 8)
+        .convolve(
+            Kernel::BoxBlur,
+            Backend::SingleNestedLoops,
+            Options::default(),
+        )
+        .unwrap();
+    assert!(single == untiled);
+}
+
+#[test]
+fn cli_rejects_budgets_for_several_kernels() {
+    let parse = |args: &[&str]| {
+        let required = [
+            "image-convolve",
+            "-i=in.png",
+            "-o=out.png",
+            "-b=auto",
+            "--memory-budget=64",
+        ];
+        Cli::try_parse_from(required.iter().chain(args))
+            .unwrap()
+            .load_kernels()
+    };
+
+    assert!(parse(&["--kernel=box-blur"]).is_ok());
+    assert!(matches!(
+        parse(&["--kernel=box-blur,sharpen"]),
+        Err(Error::UnsupportedBackend(_))
+    ));
+    assert!(matches!(
+        parse(&["--kernel=box-blur", "--passes=2"]),
+        Err(Error::UnsupportedBackend(_))
+    ));
+    assert_eq!(
+        parse(&["--kernel=box-blur,sharpen", "--merge-kernels"])
+            .unwrap()
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use clap::ValueEnum;
+use common::{assert_close, test_image};
+use image::{DynamicImage, GenericImageView};
+use image_convolve::{
+    convolution::backends::{cpu, reference::Reference},
+    kernel::KernelImpl,
+    prelude::*,
+};
+
+const CPU_BACKENDS: &[Backend] = &[
+    Backend::Auto,
+    Backend::SingleNestedLoops,
+    Backend::SingleNestedIterators,
+    Backend::SingleSimd,
+    #[cfg(feature = "rayon")]
+    Backend::MultiRayon,
+    Backend::SingleSeparable,
+    #[cfg(feature = "rayon")]
+    Backend::MultiSeparable,
+    Backend::Fft,
+];
+
+const PADDED_BORDERS: [BorderMode; 4] = [
+    BorderMode::Clamp,
+    BorderMode::Mirror,
+    BorderMode::Wrap,
+    BorderMode::Constant,
+];
+
+fn options(border: BorderMode) -> Options {
+    Options {
+        border,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn padded_borders_accept_images_smaller_than_the_kernel() {
+    for (width, height) in [(1, 1), (1, 9), (9, 1), (2, 2)] {
+        let input = test_image(width, height);
+
+        for border in PADDED_BORDERS {
+            let expected = input
+                .clone()
+                .convolve(
+                    Kernel::GaussianBlur,
+                    Backend::SingleNestedLoops,
+                    options(border),
+                )
+                .unwrap();
+            assert_eq!(expected.dimensions(), (width, height));
+
+            for &backend in CPU_BACKENDS {
+                let output = input
+                    .clone()
+                    .convolve(Kernel::GaussianBlur, backend, options(border))
+                    .unwrap();
+
+                assert_close(&output, &expected, 1e-5);
+            }
+        }
+    }
+}
+
+#[test]
+fn single_pixel_is_its_own_neighbourhood() {
+    let input = test_image(1, 1);
+
+    // Every neighbour of the only pixel is the pixel itself.
+    for border in [BorderMode::Clamp, BorderMode::Mirror, BorderMode::Wrap] {
+        let output = input
+            .clone()
+            .convolve(Kernel::BoxBlur, Backend::SingleNestedLoops, options(border))
+            .unwrap();
+
+        assert_close(&output, &input, 1e-5);
+    }
+}
+
+#[test]
+fn crop_accepts_images_as_large_as_the_kernel() {
+    let output = test_image(3, 3)
+        .convolve(Kernel::BoxBlur, Backend::Auto, options(BorderMode::Crop))
+        .unwrap();
+
+    assert_eq!(output.dimensions(), (1, 1));
+}
+
+#[test]
+fn crop_rejects_images_smaller_than_the_kernel() {
+    for &backend in CPU_BACKENDS {
+        let result = test_image(2, 5).convolve(Kernel::BoxBlur, backend, options(BorderMode::Crop));
+
+        assert!(matches!(
+            result,
+            Err(Error::ImageTooSmall {
+                width: 2,
+                height: 5,
+                kernel: (3, 3)
+            })
+        ));
+    }
+}
+
+#[test]
+fn rejects_empty_images() {
+    let input = DynamicImage::new_rgb8(0, 4);
+
+    for border in PADDED_BORDERS {
+        let tiled = Options {
+            memory_budget: Some(0),
+            ..options(border)
+        };
+
+        for options in [options(border), tiled] {
+            let result = input
+                .clone()
+                .convolve(Kernel::Identity, Backend::Auto, options);
+
+            assert!(matches!(result, Err(Error::ImageTooSmall { width: 0, .. })));
+        }
+    }
+}
+
+fn try_run<S: ConvolveStrategy>(mut backend: S) -> Result<DynamicImage> {
+    backend.convolve()?;
+    backend.finish()
+}
+
+/// The strategies can be created without [`Options::check_dimensions`],
+/// in which case images with nothing to convolve give empty outputs or errors, but never panic.
+#[test]
+fn strategies_convolve_empty_images() {
+    type Strategy = fn(DynamicImage, KernelImpl, Options) -> Result<DynamicImage>;
+    let strategies: &[(&str, Strategy)] = &[
+        ("NestedLoops", |i, k, o| {
+            try_run(cpu::single::NestedLoops::from((i, k, o)))
+        }),
+        ("NestedIterators", |i, k, o| {
+            try_run(cpu::single::NestedIterators::from((i, k, o)))
+        }),
+        ("Simd", |i, k, o| try_run(cpu::simd::Simd::from((i, k, o)))),
+        ("blocked::Single", |i, k, o| {
+            try_run(cpu::blocked::Single::from((i, k, o)))
+        }),
+        #[cfg(feature = "rayon")]
+        ("blocked::Multi", |i, k, o| {
+            try_run(cpu::blocked::Multi::from((i, k, o)))
+        }),
+        #[cfg(feature = "rayon")]
+        ("multi::NestedIterators", |i, k, o| {
+            try_run(cpu::multi::NestedIterators::from((i, k, o)))
+        }),
+        #[cfg(feature = "rayon")]
+        ("Chunked", |i, k, o| {
+            try_run(cpu::multi::Chunked::from((i, k, o)))
+        }),
+        ("separable::Single", |i, k, o| {
+            try_run(cpu::separable::Single::try_from((i, k, o))?)
+        }),
+        #[cfg(feature = "rayon")]
+        ("separable::Multi", |i, k, o| {
+            try_run(cpu::separable::Multi::try_from((i, k, o))?)
+        }),
+        ("Fft", |i, k, o| try_run(cpu::fft::Fft::from((i, k, o)))),
+        ("SummedArea", |i, k, o| {
+            try_run(cpu::summed_area::SummedArea::try_from((i, k, o))?)
+        }),
+        ("FixedPoint", |i, k, o| {
+            try_run(cpu::fixed_point::FixedPoint::try_from((i, k, o))?)
+        }),
+        ("Reference", |i, k, o| try_run(Reference::from((i, k, o)))),
+    ];
+
+    let kernel = KernelImpl::box_blur(3, 3).unwrap();
+    for (width, height) in [(0, 4), (4, 0), (0, 0), (2, 5)] {
+        let input = DynamicImage::new_rgb8(width, height);
+
+        for &border in BorderMode::value_variants() {
+            let options = Options {
+                border,
+                color_space: ColorSpace::Srgb,
+                ..Options::default()
+            };
+            let (width, height) = options.output_dimensions(input.dimensions(), &kernel);
+            if width * height != 0 {
+                continue;
+            }
+
+            for &(name, strategy) in strategies {
+                match strategy(input.clone(), kernel.clone(), options) {
+                    Ok(output) => {
+                        assert_eq!(
+                            output.dimensions(),
+                            (width, height),
+                            "{name} with {border:?}"
+                        )
+                    }
+                    // E.g. [`cpu::summed_area::SummedArea`] checks every pass.
+                    Err(Error::ImageTooSmall { .. }) => {}
+                    Err(e) => panic!("{name} with {border:?}: {e}"),
+                }
+            }
+        }
+    }
+}